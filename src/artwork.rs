@@ -0,0 +1,189 @@
+//! Cover images stored in the `Artwork` tag
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::mp4::{CoverArt, ImageFormat};
+use crate::{Atom, AtomKind, AtomValue, Atoms, Result, SublerError};
+
+/// Where the image of an `Artwork` comes from
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    Path(PathBuf),
+    Data(Vec<u8>),
+}
+
+/// A cover image, read from a file or held in memory
+///
+/// The format is detected from the magic bytes, not the file extension:
+///
+/// ```rust
+/// use sublercli::{mp4::ImageFormat, Artwork, SublerError};
+/// let png = Artwork::from_bytes(b"\x89PNG\r\n\x1a\n....".to_vec());
+/// assert_eq!(png.format().unwrap(), ImageFormat::Png);
+///
+/// match Artwork::from_bytes(b"GIF89a".to_vec()).format() {
+///     Err(SublerError::InvalidArtwork(_)) => {}
+///     other => panic!("{:?}", other),
+/// }
+/// match Artwork::from_path("missing-cover.jpg").format() {
+///     Err(SublerError::ArtworkNotFound(_)) => {}
+///     other => panic!("{:?}", other),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Artwork {
+    source: Source,
+}
+
+impl Artwork {
+    /// The image in the file at `path`
+    pub fn from_path<P: Into<PathBuf>>(path: P) -> Artwork {
+        Artwork {
+            source: Source::Path(path.into()),
+        }
+    }
+
+    /// The encoded image in `data`
+    ///
+    /// SublerCli only reads images from files, in-memory images are written to a
+    /// temporary file for the run.
+    pub fn from_bytes<D: Into<Vec<u8>>>(data: D) -> Artwork {
+        Artwork {
+            source: Source::Data(data.into()),
+        }
+    }
+
+    /// The path of an image read from a file
+    pub fn path(&self) -> Option<&Path> {
+        match &self.source {
+            Source::Path(path) => Some(path),
+            Source::Data(_) => None,
+        }
+    }
+
+    /// The encoded image of an in-memory image
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match &self.source {
+            Source::Path(_) => None,
+            Source::Data(data) => Some(data),
+        }
+    }
+
+    /// The artwork with a relative path taken as relative to `dir`
+    pub fn resolve(&self, dir: &Path) -> Artwork {
+        match &self.source {
+            Source::Path(path) if path.is_relative() => Artwork::from_path(dir.join(path)),
+            _ => self.clone(),
+        }
+    }
+
+    /// Detects the format of the image, only the start of a file is read
+    ///
+    /// Fails with `SublerError::ArtworkNotFound` if the file doesn't exist and with
+    /// `SublerError::InvalidArtwork` if the image is not a JPEG, PNG or BMP.
+    pub fn format(&self) -> Result<ImageFormat> {
+        let mut magic = Vec::new();
+        let data = match &self.source {
+            Source::Path(path) => {
+                if !path.is_file() {
+                    return Err(SublerError::ArtworkNotFound(path.clone()));
+                }
+                File::open(path)?.take(8).read_to_end(&mut magic)?;
+                &magic
+            }
+            Source::Data(data) => data,
+        };
+        ImageFormat::sniff(data).ok_or_else(|| SublerError::InvalidArtwork(self.to_string()))
+    }
+
+    /// Reads the image, see `format` for the errors
+    pub fn load(&self) -> Result<CoverArt> {
+        let format = self.format()?;
+        let data = match &self.source {
+            Source::Path(path) => fs::read(path)?,
+            Source::Data(data) => data.clone(),
+        };
+        Ok(CoverArt { format, data })
+    }
+}
+
+/// The path of an image file, or the size of an in-memory image
+impl fmt::Display for Artwork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.source {
+            Source::Path(path) => write!(f, "{}", path.display()),
+            Source::Data(data) => write!(f, "<{} bytes in memory>", data.len()),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for Artwork {
+    fn from(path: &'a str) -> Self {
+        Artwork::from_path(path)
+    }
+}
+
+impl From<String> for Artwork {
+    fn from(path: String) -> Self {
+        Artwork::from_path(path)
+    }
+}
+
+impl<'a> From<&'a Path> for Artwork {
+    fn from(path: &'a Path) -> Self {
+        Artwork::from_path(path)
+    }
+}
+
+impl From<PathBuf> for Artwork {
+    fn from(path: PathBuf) -> Self {
+        Artwork::from_path(path)
+    }
+}
+
+/// The cover of an `Artwork` atom, also if the path was set as text
+pub(crate) fn atom_artwork(atom: &Atom) -> Option<Artwork> {
+    if atom.is_deletion() || Atoms::tag_kind(&atom.tag) != Some(AtomKind::Image) {
+        return None;
+    }
+    match &atom.value {
+        AtomValue::Image(artwork) => Some(artwork.clone()),
+        AtomValue::Text(path) => Some(Artwork::from_path(path.as_str())),
+        _ => None,
+    }
+}
+
+impl Atoms {
+    /// All artworks, in the order they are stored
+    ///
+    /// Image paths set as text, like with `Atom::new("Artwork", "cover.jpg")`, are included.
+    pub fn artworks(&self) -> Vec<Artwork> {
+        self.atoms().iter().filter_map(atom_artwork).collect()
+    }
+
+    /// Takes relative artwork paths as relative to `dir`, `Subler` resolves them against
+    /// the directory of the source file
+    ///
+    /// ```rust
+    /// use std::path::Path;
+    /// use sublercli::*;
+    /// let mut atoms = Atoms::new()
+    ///     .artwork("cover.jpg")
+    ///     .add_artwork(Path::new("/covers/back.png"))
+    ///     .build();
+    /// atoms.resolve_artwork(Path::new("/movies"));
+    /// let paths: Vec<_> = atoms.artworks().iter().map(|a| a.to_string()).collect();
+    /// assert_eq!(paths, vec!["/movies/cover.jpg", "/covers/back.png"]);
+    /// ```
+    pub fn resolve_artwork(&mut self, dir: &Path) -> &mut Self {
+        for atom in self.atoms_mut() {
+            if let Some(artwork) = atom_artwork(atom) {
+                atom.value = AtomValue::Image(artwork.resolve(dir));
+            }
+        }
+        self
+    }
+}
@@ -0,0 +1,783 @@
+//! Backends that perform the actual tagging of a file
+//!
+//! `Subler` resolves its configuration into a `TagRequest` and hands it to a
+//! `TagBackend`. By default this is the `SublerCli` process, but any other
+//! implementation can be plugged in with `Subler::backend`:
+//!
+//! ```rust,no_run
+//! use std::sync::Mutex;
+//! use sublercli::*;
+//!
+//! /// Records every request instead of tagging anything
+//! #[derive(Debug, Default)]
+//! struct Recorder {
+//!     requests: Mutex<Vec<TagRequest>>,
+//! }
+//!
+//! impl TagBackend for Recorder {
+//!     fn tag(&self, request: &TagRequest) -> Result<TagOutcome> {
+//!         self.requests.lock().unwrap().push(request.clone());
+//!         Ok(TagOutcome::new(request.dest.clone()))
+//!     }
+//! }
+//!
+//! let outcome = Subler::new("demo.mp4", Atoms::new().title("Foo").build())
+//!     .backend(Recorder::default())
+//!     .tag()
+//!     .unwrap();
+//! ```
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel::Abort;
+use crate::mp4::ImageFormat;
+use crate::progress::ProgressParser;
+use crate::{
+    chapters, mp4, Artwork, AtomValue, Atoms, CancelHandle, Chapter, Downmix, Phase,
+    ProgressCallback, Result, Subler, SublerError, Subtitle, TagOutcome,
+};
+
+/// How often a running process is checked for a timeout or cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The number of the next `TagRequest`, see `TagRequest::run`
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// Performs the tagging described by a `TagRequest`
+///
+/// Backends are `Send` and `Sync`, so a configured `Subler` can be moved to other threads.
+pub trait TagBackend: fmt::Debug + Send + Sync {
+    /// Writes the `request.atoms` of `request.source` to `request.dest`
+    fn tag(&self, request: &TagRequest) -> Result<TagOutcome>;
+
+    /// The SublerCli process this backend runs, which `Subler::tag_async` runs with
+    /// `tokio::process` instead of blocking a thread
+    fn sublercli(&self) -> Option<&SublerCli> {
+        None
+    }
+}
+
+/// A fully resolved tagging job, as handed to a `TagBackend`
+#[derive(Debug, Clone)]
+pub struct TagRequest {
+    /// The path to the existing source file
+    pub source: PathBuf,
+    /// The path the tagged file is written to
+    pub dest: PathBuf,
+    /// The atoms to write, including the Media Kind
+    pub atoms: Atoms,
+    /// Whether the output should be optimized
+    pub optimize: bool,
+    /// Whether all existing metadata is removed before the atoms are written
+    pub remove_existing: bool,
+    /// The audio downmix, if any
+    pub downmix: Option<Downmix>,
+    /// Whether tracks are enabled and grouped the way iTunes expects
+    pub itunes_friendly: bool,
+    /// Whether tracks are organized into alternate groups
+    pub organize_groups: bool,
+    /// The chapter markers to import
+    pub chapters: Vec<Chapter>,
+    /// The subtitle files to add as tracks
+    pub subtitles: Vec<Subtitle>,
+    /// The longest the whole run may take, no limit if `None`
+    pub timeout: Option<Duration>,
+    /// Cancels the run when cancelled from another thread
+    pub cancel: CancelHandle,
+    /// Receives the progress of the run, if any
+    pub progress: Option<ProgressCallback>,
+    /// Numbers the runs of the process, keeps the temporary files of concurrent runs apart
+    pub run: usize,
+}
+
+impl TagRequest {
+    /// A `run` number that no other request of the process has
+    pub(crate) fn next_run() -> usize {
+        NEXT_RUN.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// The temporary file the chapters are written to for SublerCli
+    ///
+    /// Every request has its own, so runs tagging to the same destination don't interfere.
+    pub fn chapter_file(&self) -> PathBuf {
+        self.temp_file("chapters.txt")
+    }
+
+    /// The temporary file the `index`th in-memory artwork is written to for SublerCli
+    pub fn artwork_file(&self, index: usize, format: ImageFormat) -> PathBuf {
+        self.temp_file(&format!("artwork-{}.{}", index, format.extension()))
+    }
+
+    /// A temporary file named after the process, the run and the destination
+    fn temp_file(&self, suffix: &str) -> PathBuf {
+        let name = self
+            .dest
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        env::temp_dir().join(format!(
+            "sublercli-{}-{}-{}.{}",
+            process::id(),
+            self.run,
+            name,
+            suffix
+        ))
+    }
+
+    /// The in-memory artworks with the temporary files they are written to for SublerCli
+    fn artwork_files(&self) -> Result<Vec<(PathBuf, &[u8])>> {
+        let mut files = Vec::new();
+        for atom in self.atoms.atoms() {
+            if let AtomValue::Image(artwork) = &atom.value {
+                if let Some(data) = artwork.bytes() {
+                    files.push((self.artwork_file(files.len(), artwork.format()?), data));
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// The atoms as passed to SublerCli, in-memory artworks point to their temporary files
+    fn cli_atoms(&self) -> Result<Atoms> {
+        let mut files = self.artwork_files()?.into_iter().map(|(path, _)| path);
+        let mut atoms = self.atoms.clone();
+        for atom in atoms.atoms_mut() {
+            if let AtomValue::Image(artwork) = &mut atom.value {
+                if artwork.bytes().is_some() {
+                    *artwork = Artwork::from_path(files.next().unwrap_or_default());
+                }
+            }
+        }
+        Ok(atoms)
+    }
+
+    /// Whether SublerCli reads some of the request from temporary files, see `SublerCli::prepare`
+    pub(crate) fn has_temp_files(&self) -> bool {
+        !self.chapters.is_empty() || self.artwork_files().map_or(true, |files| !files.is_empty())
+    }
+
+    /// Removes the temporary files written by `SublerCli::prepare`
+    pub fn remove_temp_files(&self) {
+        if !self.chapters.is_empty() {
+            let _ = fs::remove_file(self.chapter_file());
+        }
+        for (path, _) in self.artwork_files().unwrap_or_default() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Starts the clock for the `timeout` of the run
+    pub(crate) fn abort(&self) -> Abort {
+        Abort::new(&self.cancel, self.timeout)
+    }
+
+    /// Removes the partially written destination if the run was aborted
+    fn cleanup_aborted(&self, err: SublerError) -> SublerError {
+        if let SublerError::Timeout(_) | SublerError::Cancelled = err {
+            let _ = fs::remove_file(&self.dest);
+        }
+        err
+    }
+}
+
+/// Reads a pipe of a child process to its end on a separate thread
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// Forwards the output of a pipe of a child process chunk by chunk, as it is written
+fn stream_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> mpsc::Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut pipe = match pipe {
+            Some(pipe) => pipe,
+            None => return,
+        };
+        let mut buf = [0; 8192];
+        loop {
+            match pipe.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if tx.send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    rx
+}
+
+/// Tags files by running the SublerCli executable
+#[derive(Debug, Clone)]
+pub struct SublerCli {
+    /// The path to the SublerCli executable
+    pub executable: PathBuf,
+}
+
+impl SublerCli {
+    /// Uses the executable at `Subler::cli_executeable()`
+    pub fn new() -> Self {
+        SublerCli::with_executable(Subler::cli_executeable())
+    }
+
+    /// Uses the executable at the given path
+    pub fn with_executable<P: Into<PathBuf>>(executable: P) -> Self {
+        SublerCli {
+            executable: executable.into(),
+        }
+    }
+
+    /// create the subler process command for the request
+    ///
+    /// fails with `SublerError::AmbiguousAtom` if an atom can't be passed to SublerCli
+    pub fn command(&self, request: &TagRequest) -> Result<Command> {
+        let mut cmd = Command::new(&self.executable);
+        cmd.arg("-source")
+            .arg(&request.source)
+            .arg("-dest")
+            .arg(&request.dest);
+        if request.remove_existing {
+            cmd.arg("-remove");
+        }
+        if !request.chapters.is_empty() {
+            cmd.arg("-chapters").arg(request.chapter_file());
+        }
+        cmd.args(request.cli_atoms()?.args()?);
+        // the subtitle commands rewrite the destination, the last of them optimizes it
+        if request.optimize && request.subtitles.is_empty() {
+            cmd.arg("-optimize");
+        }
+        if let Some(downmix) = request.downmix {
+            cmd.arg("-downmix").arg(downmix.as_str());
+        }
+        if request.itunes_friendly {
+            cmd.arg("-itunesfriendly");
+        }
+        if request.organize_groups {
+            cmd.arg("-organizegroups");
+        }
+        Ok(cmd)
+    }
+
+    /// create the commands that add the subtitle tracks of the request to its destination
+    ///
+    /// SublerCli takes the subtitle options once per invocation, so every track is added
+    /// by its own command, run after the tagging command. The last one carries `-optimize`,
+    /// so the optimized layout isn't undone by a later command.
+    ///
+    /// ```rust,no_run
+    /// use sublercli::*;
+    /// let mut subler = Subler::new("demo.mp4", Atoms::new().build());
+    /// subler.subtitle(Subtitle::new("demo.en.srt")).subtitle(Subtitle::new("demo.de.srt"));
+    /// for line in subler.dry_run().unwrap() {
+    ///     println!("{}", line);
+    /// }
+    /// ```
+    pub fn subtitle_commands(&self, request: &TagRequest) -> Vec<Command> {
+        let last = request.subtitles.len().saturating_sub(1);
+        request
+            .subtitles
+            .iter()
+            .enumerate()
+            .map(|(index, subtitle)| {
+                let mut cmd = Command::new(&self.executable);
+                cmd.arg("-source")
+                    .arg(&subtitle.path)
+                    .arg("-dest")
+                    .arg(&request.dest)
+                    .args(subtitle.args());
+                if request.optimize && index == last {
+                    cmd.arg("-optimize");
+                }
+                cmd
+            })
+            .collect()
+    }
+
+    /// Runs a command to completion, failing on a non-zero exit status
+    ///
+    /// returns the captured stdout and stderr. The process is killed as soon as `abort` fires.
+    /// The stdout is fed to `progress` while the process runs.
+    fn run(
+        &self,
+        mut cmd: Command,
+        abort: &Abort,
+        mut progress: Option<&mut ProgressParser>,
+    ) -> Result<(String, String)> {
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| self.spawn_error(err))?;
+        let chunks = stream_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+        let mut stdout = Vec::new();
+        let mut on_output = |chunk: Vec<u8>| {
+            if let Some(progress) = progress.as_deref_mut() {
+                progress.feed(&chunk);
+            }
+            stdout.extend(chunk);
+        };
+        let status = loop {
+            if let Err(err) = abort.check() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err);
+            }
+            match chunks.recv_timeout(POLL_INTERVAL) {
+                Ok(chunk) => {
+                    on_output(chunk);
+                    continue;
+                }
+                // the stdout is closed, but the process may still be running
+                Err(RecvTimeoutError::Disconnected) => thread::sleep(POLL_INTERVAL),
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+        };
+        chunks.into_iter().for_each(&mut on_output);
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+        self.captured(Ok(Output {
+            status,
+            stdout,
+            stderr: stderr.join().unwrap_or_default(),
+        }))
+    }
+
+    /// Checks the exit status of a finished process and returns its stdout and stderr
+    fn captured(&self, output: io::Result<Output>) -> Result<(String, String)> {
+        let output = output.map_err(|err| self.spawn_error(err))?;
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        if !output.status.success() {
+            return Err(SublerError::CliFailed {
+                status: output.status,
+                stderr,
+            });
+        }
+        Ok((stdout, stderr))
+    }
+
+    /// Writes the files the command for the request refers to, like the chapter file and
+    /// in-memory artworks
+    pub fn prepare(&self, request: &TagRequest) -> Result<()> {
+        if !request.chapters.is_empty() {
+            chapters::write_chapter_file(request.chapter_file(), &request.chapters)?;
+        }
+        for (path, data) in request.artwork_files()? {
+            fs::write(path, data)?;
+        }
+        Ok(())
+    }
+
+    /// The names of all languages SublerCli knows, from `-listlanguages`
+    pub fn list_languages(&self) -> Result<Vec<String>> {
+        let mut cmd = Command::new(&self.executable);
+        cmd.arg("-listlanguages");
+        let (stdout, _) = self.run(cmd, &Abort::never(), None)?;
+        Ok(stdout
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_owned)
+            .collect())
+    }
+
+    /// Reads the existing metadata of the file at `path` with `-listmetadata`
+    pub fn list_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Atoms> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(SublerError::SourceNotFound(path.to_path_buf()));
+        }
+        let mut cmd = Command::new(&self.executable);
+        cmd.arg("-source").arg(path).arg("-listmetadata");
+        let (stdout, _) = self.run(cmd, &Abort::never(), None)?;
+        Ok(Atoms::from_metadata_listing(&stdout))
+    }
+
+    /// maps a failed process spawn to `SublerError::ExecutableNotFound` if the
+    /// executable is missing
+    pub(crate) fn spawn_error(&self, err: io::Error) -> SublerError {
+        if err.kind() == io::ErrorKind::NotFound {
+            SublerError::ExecutableNotFound(self.executable.clone())
+        } else {
+            SublerError::Io(err)
+        }
+    }
+}
+
+impl Default for SublerCli {
+    fn default() -> Self {
+        SublerCli::new()
+    }
+}
+
+impl TagBackend for SublerCli {
+    fn sublercli(&self) -> Option<&SublerCli> {
+        Some(self)
+    }
+
+    fn tag(&self, request: &TagRequest) -> Result<TagOutcome> {
+        let abort = request.abort();
+        let mut progress = request.progress.clone().map(ProgressParser::new);
+        let command = self.command(request)?;
+        self.prepare(request)?;
+        let start = Instant::now();
+        if let Some(progress) = progress.as_mut() {
+            progress.enter(Phase::Muxing);
+        }
+        let output = self.run(command, &abort, progress.as_mut());
+        request.remove_temp_files();
+        let output = output.and_then(|(mut stdout, mut stderr)| {
+            for cmd in self.subtitle_commands(request) {
+                if let Some(progress) = progress.as_mut() {
+                    progress.enter(Phase::Subtitles);
+                }
+                let (out, err) = self.run(cmd, &abort, progress.as_mut())?;
+                stdout.push_str(&out);
+                stderr.push_str(&err);
+            }
+            Ok((stdout, stderr))
+        });
+        let (stdout, stderr) = output.map_err(|err| request.cleanup_aborted(err))?;
+        Ok(TagOutcome::from_output(request, stdout, stderr, start))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl SublerCli {
+    /// Tags like `TagBackend::tag`, but runs the processes with `tokio::process`
+    pub async fn tag_async(&self, request: &TagRequest) -> Result<TagOutcome> {
+        let abort = request.abort();
+        let mut progress = request.progress.clone().map(ProgressParser::new);
+        let command = self.command(request)?;
+        self.prepare(request)?;
+        let start = Instant::now();
+        if let Some(progress) = progress.as_mut() {
+            progress.enter(Phase::Muxing);
+        }
+        let output = self.run_async(command, &abort, progress.as_mut()).await;
+        request.remove_temp_files();
+        let output = async {
+            let (mut stdout, mut stderr) = output?;
+            for cmd in self.subtitle_commands(request) {
+                if let Some(progress) = progress.as_mut() {
+                    progress.enter(Phase::Subtitles);
+                }
+                let (out, err) = self.run_async(cmd, &abort, progress.as_mut()).await?;
+                stdout.push_str(&out);
+                stderr.push_str(&err);
+            }
+            Ok((stdout, stderr))
+        }
+        .await;
+        let (stdout, stderr) = output.map_err(|err| request.cleanup_aborted(err))?;
+        Ok(TagOutcome::from_output(request, stdout, stderr, start))
+    }
+
+    /// Runs a command to completion without blocking, failing on a non-zero exit status
+    ///
+    /// The process is killed as soon as `abort` fires, the stdout is fed to `progress`
+    /// while the process runs.
+    async fn run_async(
+        &self,
+        cmd: Command,
+        abort: &Abort,
+        mut progress: Option<&mut ProgressParser>,
+    ) -> Result<(String, String)> {
+        use tokio::io::AsyncReadExt;
+
+        // dropping the child on an early return kills it
+        let mut child = tokio::process::Command::from(cmd)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| self.spawn_error(err))?;
+        let mut stdout_pipe = child.stdout.take();
+        let mut stderr_pipe = child.stderr.take();
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let (mut out_buf, mut err_buf) = ([0; 8192], [0; 8192]);
+        let status = loop {
+            abort.check()?;
+            let slice = abort
+                .remaining()
+                .map_or(POLL_INTERVAL, |r| r.min(POLL_INTERVAL));
+            tokio::select! {
+                read = async { stdout_pipe.as_mut().unwrap().read(&mut out_buf).await },
+                    if stdout_pipe.is_some() =>
+                {
+                    match read? {
+                        0 => stdout_pipe = None,
+                        n => {
+                            if let Some(progress) = progress.as_deref_mut() {
+                                progress.feed(&out_buf[..n]);
+                            }
+                            stdout.extend_from_slice(&out_buf[..n]);
+                        }
+                    }
+                }
+                read = async { stderr_pipe.as_mut().unwrap().read(&mut err_buf).await },
+                    if stderr_pipe.is_some() =>
+                {
+                    match read? {
+                        0 => stderr_pipe = None,
+                        n => stderr.extend_from_slice(&err_buf[..n]),
+                    }
+                }
+                status = child.wait(), if stdout_pipe.is_none() && stderr_pipe.is_none() => {
+                    break status;
+                }
+                _ = tokio::time::sleep(slice) => {}
+            }
+        };
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+        self.captured(status.map(|status| Output {
+            status,
+            stdout,
+            stderr,
+        }))
+    }
+}
+
+/// Tags files with the pure Rust MP4 writer in `mp4`, without SublerCli
+///
+/// Runs on every platform, but only supports `.mp4`, `.m4v` and `.m4a` files.
+/// `TagRequest::optimize` is implemented with `mp4::optimize_file`'s faststart layout,
+/// chapters, subtitles, downmixing and track organization are not supported.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NativeBackend;
+
+impl TagBackend for NativeBackend {
+    fn tag(&self, request: &TagRequest) -> Result<TagOutcome> {
+        if !request.chapters.is_empty() {
+            return Err(SublerError::Unsupported("Chapter import"));
+        }
+        if !request.subtitles.is_empty() {
+            return Err(SublerError::Unsupported("Subtitle import"));
+        }
+        if request.downmix.is_some() {
+            return Err(SublerError::Unsupported("Audio downmix"));
+        }
+        if request.itunes_friendly || request.organize_groups {
+            return Err(SublerError::Unsupported("Track organization"));
+        }
+        let start = Instant::now();
+        mp4::write(
+            &request.source,
+            &request.dest,
+            Some(&request.atoms),
+            request.remove_existing,
+            request.optimize,
+            &request.abort(),
+        )?;
+        let mut outcome = TagOutcome::new(request.dest.clone());
+        outcome.elapsed = start.elapsed();
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::test_util;
+
+    /// Records every request instead of tagging anything
+    #[derive(Debug, Default, Clone)]
+    struct Recorder {
+        requests: Arc<Mutex<Vec<TagRequest>>>,
+    }
+
+    impl TagBackend for Recorder {
+        fn tag(&self, request: &TagRequest) -> Result<TagOutcome> {
+            self.requests.lock().unwrap().push(request.clone());
+            Ok(TagOutcome::new(request.dest.clone()))
+        }
+    }
+
+    #[test]
+    fn custom_backends_receive_the_request() {
+        let source = test_util::source_file("custom-backend");
+        let recorder = Recorder::default();
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().title("Foo").build());
+        subler.backend(recorder.clone());
+        let outcome = subler.tag().unwrap();
+        assert_eq!(outcome.dest, source.with_file_name("demo.0.mp4"));
+        let requests = recorder.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].source, source);
+        assert!(requests[0].atoms.get("Name").is_some());
+    }
+
+    #[test]
+    fn configured_sublers_move_to_other_threads() {
+        let source = test_util::source_file("backend-thread");
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        subler.backend(Recorder::default());
+        thread::spawn(move || subler.tag().unwrap()).join().unwrap();
+    }
+
+    #[test]
+    fn requests_have_their_own_temporary_files() {
+        let source = test_util::source_file("temp-files");
+        let subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        let (first, second) = (subler.request().unwrap(), subler.request().unwrap());
+        assert_ne!(first.chapter_file(), second.chapter_file());
+        assert_ne!(
+            first.artwork_file(0, ImageFormat::Png),
+            second.artwork_file(0, ImageFormat::Png)
+        );
+    }
+
+    #[test]
+    fn optimizes_with_the_last_subtitle_command() {
+        let source = test_util::source_file("subtitle-commands");
+        let (en, de) = (
+            source.with_file_name("en.srt"),
+            source.with_file_name("de.srt"),
+        );
+        fs::write(&en, b"").unwrap();
+        fs::write(&de, b"").unwrap();
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        subler
+            .subtitle(Subtitle::new(&en))
+            .subtitle(Subtitle::new(&de));
+        let lines = subler.dry_run().unwrap();
+        let optimized: Vec<_> = lines.iter().map(|l| l.ends_with(" -optimize")).collect();
+        assert_eq!(optimized, vec![false, false, true]);
+        assert!(lines[2].starts_with(&format!(
+            "{} -source {}",
+            subler.cli().executable.display(),
+            de.display()
+        )));
+    }
+
+    #[test]
+    fn native_backend_removes_existing_metadata() {
+        let source = test_util::mp4_file("native-remove-existing");
+        let tagged = source.with_file_name("tagged.mp4");
+        let atoms = Atoms::new().title("Foo").artist("Bar").build();
+        mp4::write_metadata(&source, &tagged, &atoms).unwrap();
+
+        let mut subler = Subler::new(
+            tagged.to_str().unwrap(),
+            Atoms::new().genre("Drama").build(),
+        );
+        let outcome = subler
+            .backend(NativeBackend)
+            .remove_existing(true)
+            .tag()
+            .unwrap();
+        let written = mp4::read_metadata(&outcome.dest).unwrap();
+        assert!(written.get("Name").is_none() && written.get("Artist").is_none());
+        assert_eq!(written.get("Genre").unwrap().value.to_string(), "Drama");
+    }
+
+    /// Tags with a fake SublerCli running `script`
+    #[cfg(unix)]
+    fn fake_subler(name: &str, script: &str) -> Subler {
+        let source = test_util::source_file(name);
+        let cli = test_util::fake_cli(source.parent().unwrap(), script);
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        subler.backend(SublerCli::with_executable(cli));
+        subler
+    }
+
+    /// Tags with a fake SublerCli that writes part of the destination and hangs
+    #[cfg(unix)]
+    fn hanging_subler(name: &str) -> Subler {
+        fake_subler(name, "echo partial > \"$dest\"\nexec sleep 10")
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn reports_a_failed_run_with_its_stderr() {
+        let mut subler = fake_subler("cli-failed", "echo 'Error: no track found' >&2\nexit 3");
+        match subler.tag() {
+            Err(SublerError::CliFailed { status, stderr }) => {
+                assert_eq!(status.code(), Some(3));
+                assert_eq!(stderr, "Error: no track found\n");
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn collects_the_warnings_of_a_successful_run() {
+        let script = [
+            "echo 'Muxing 100%'",
+            "echo 'Warning: no chapters'",
+            "echo 'language undefined' >&2",
+            "cp /dev/null \"$dest\"",
+        ]
+        .join("\n");
+        let mut subler = fake_subler("cli-warnings", &script);
+        let outcome = subler.tag().unwrap();
+        assert!(outcome.dest.exists());
+        assert_eq!(
+            outcome.warnings,
+            vec!["Warning: no chapters", "language undefined"]
+        );
+        assert_eq!(outcome.stderr, "language undefined\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn removes_the_destination_after_a_timeout() {
+        let mut subler = hanging_subler("cli-timeout");
+        let dest = subler.request().unwrap().dest;
+        let timeout = Duration::from_millis(300);
+        match subler.timeout(Some(timeout)).tag() {
+            Err(SublerError::Timeout(t)) => assert_eq!(t, timeout),
+            other => panic!("{:?}", other),
+        }
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn removes_the_destination_after_a_cancel() {
+        let mut subler = hanging_subler("cli-cancel");
+        let dest = subler.request().unwrap().dest;
+        let cancel = subler.cancel_handle();
+        let written = dest.clone();
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            assert!(written.exists());
+            cancel.cancel();
+        });
+        match subler.tag() {
+            Err(SublerError::Cancelled) => {}
+            other => panic!("{:?}", other),
+        }
+        canceller.join().unwrap();
+        assert!(!dest.exists());
+    }
+}
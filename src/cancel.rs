@@ -0,0 +1,85 @@
+//! Cancellation and timeouts of tagging runs
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{Result, SublerError};
+
+/// A handle to cancel a running tagging process from another thread
+///
+/// Obtained with `Subler::cancel_handle`. A cancelled handle stays cancelled until `reset`.
+///
+/// ```rust
+/// use sublercli::*;
+/// let subler = Subler::new("demo.mp4", Atoms::new().build());
+/// let handle = subler.cancel_handle();
+/// std::thread::spawn(move || handle.cancel());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        CancelHandle::default()
+    }
+
+    /// Requests the cancellation of the tagging run
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the cancellation was requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Clears a previous cancellation, so the handle can be used for another run
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Decides whether a running operation has to be aborted
+#[derive(Debug, Clone)]
+pub(crate) struct Abort {
+    cancel: CancelHandle,
+    timeout: Option<(Duration, Instant)>,
+}
+
+impl Abort {
+    /// Aborts once `cancel` is cancelled or `timeout` has passed from now
+    pub fn new(cancel: &CancelHandle, timeout: Option<Duration>) -> Self {
+        Abort {
+            cancel: cancel.clone(),
+            timeout: timeout.map(|t| (t, Instant::now() + t)),
+        }
+    }
+
+    /// Never aborts
+    pub fn never() -> Self {
+        Abort::new(&CancelHandle::new(), None)
+    }
+
+    /// The time left until the timeout, if any
+    #[cfg(feature = "tokio")]
+    pub fn remaining(&self) -> Option<Duration> {
+        self.timeout
+            .map(|(_, deadline)| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Fails with `Cancelled` or `Timeout` if the operation has to be aborted
+    pub fn check(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(SublerError::Cancelled);
+        }
+        match self.timeout {
+            Some((timeout, deadline)) if Instant::now() >= deadline => {
+                Err(SublerError::Timeout(timeout))
+            }
+            _ => Ok(()),
+        }
+    }
+}
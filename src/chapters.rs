@@ -0,0 +1,176 @@
+//! Chapter markers and the OGM style chapter text files SublerCli imports with `-chapters`
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use sublercli::chapters::{self, Chapter};
+//!
+//! let chapters = vec![
+//!     Chapter::new(Duration::from_secs(0), "Intro"),
+//!     Chapter::new(Duration::from_millis(95_500), "Act 1"),
+//! ];
+//! let text = chapters::format_chapters(&chapters);
+//! assert_eq!(
+//!     text,
+//!     "CHAPTER01=00:00:00.000\nCHAPTER01NAME=Intro\nCHAPTER02=00:01:35.500\nCHAPTER02NAME=Act 1\n"
+//! );
+//! assert_eq!(chapters::parse_chapters(&text).unwrap(), chapters);
+//!
+//! // the simple `timestamp title` form is understood as well
+//! let parsed = chapters::parse_chapters("00:00:00.000 Intro\n00:01:35.500 Act 1").unwrap();
+//! assert_eq!(parsed, chapters);
+//! ```
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::{Result, SublerError};
+
+/// A chapter marker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    /// The offset of the chapter from the start of the file
+    pub start: Duration,
+    /// The title of the chapter
+    pub title: String,
+}
+
+impl Chapter {
+    pub fn new(start: Duration, title: &str) -> Self {
+        Chapter {
+            start,
+            title: title.to_owned(),
+        }
+    }
+}
+
+/// Renders a duration as `HH:MM:SS.mmm`
+fn format_timestamp(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        time.subsec_millis()
+    )
+}
+
+/// Parses a `HH:MM:SS.mmm` timestamp, hours and fractions are optional
+fn parse_timestamp(s: &str) -> Option<Duration> {
+    let (clock, fraction) = match s.find('.') {
+        Some(pos) => (&s[..pos], &s[pos + 1..]),
+        None => (s, ""),
+    };
+    let parts: Vec<u64> = clock
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let secs = match parts.as_slice() {
+        [h, m, s] if *m < 60 && *s < 60 => h * 3600 + m * 60 + s,
+        [m, s] if *s < 60 => m * 60 + s,
+        _ => return None,
+    };
+    let millis = if fraction.is_empty() {
+        0
+    } else if fraction.len() <= 9 && fraction.bytes().all(|b| b.is_ascii_digit()) {
+        // pad or cut the fraction to milliseconds
+        format!("{:0<3}", fraction)[..3].parse().ok()?
+    } else {
+        return None;
+    };
+    Some(Duration::from_secs(secs) + Duration::from_millis(millis))
+}
+
+/// Renders the chapters in the OGM chapter format
+pub fn format_chapters(chapters: &[Chapter]) -> String {
+    let mut text = String::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        let number = index + 1;
+        text.push_str(&format!(
+            "CHAPTER{:02}={}\nCHAPTER{:02}NAME={}\n",
+            number,
+            format_timestamp(chapter.start),
+            number,
+            chapter.title
+        ));
+    }
+    text
+}
+
+/// Parses chapters in the OGM format (`CHAPTER01=00:00:00.000` followed by
+/// `CHAPTER01NAME=Title`) or in the simple `00:00:00.000 Title` format
+pub fn parse_chapters(text: &str) -> Result<Vec<Chapter>> {
+    let invalid = |line: &str| SublerError::InvalidChapters(line.to_owned());
+    let mut chapters: Vec<Chapter> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let ogm = line
+            .get(..7)
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case("CHAPTER"));
+        if ogm && line.len() > 7 {
+            let (key, value) = match line.find('=') {
+                Some(pos) if pos >= 7 => (&line[7..pos], &line[pos + 1..]),
+                _ => return Err(invalid(line)),
+            };
+            let name = key
+                .len()
+                .checked_sub(4)
+                .filter(|start| *start > 0)
+                .and_then(|start| key.get(start..))
+                .is_some_and(|suffix| suffix.eq_ignore_ascii_case("NAME"));
+            if name {
+                let last = chapters.last_mut().ok_or_else(|| invalid(line))?;
+                last.title = value.trim().to_owned();
+            } else {
+                let start = parse_timestamp(value.trim()).ok_or_else(|| invalid(line))?;
+                chapters.push(Chapter::new(start, ""));
+            }
+        } else {
+            let (time, title) = match line.find(char::is_whitespace) {
+                Some(pos) => (&line[..pos], line[pos..].trim()),
+                None => (line, ""),
+            };
+            let start = parse_timestamp(time).ok_or_else(|| invalid(line))?;
+            chapters.push(Chapter::new(start, title));
+        }
+    }
+    Ok(chapters)
+}
+
+/// Reads a chapter file in one of the formats understood by `parse_chapters`
+pub fn read_chapter_file<P: AsRef<Path>>(path: P) -> Result<Vec<Chapter>> {
+    parse_chapters(&fs::read_to_string(path)?)
+}
+
+/// Writes the chapters to a file in the OGM chapter format
+pub fn write_chapter_file<P: AsRef<Path>>(path: P, chapters: &[Chapter]) -> Result<()> {
+    fs::write(path, format_chapters(chapters))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_non_ascii_lines_without_panicking() {
+        // the name suffix check would cut the `é` in the second one
+        for text in &["Résumé 00:00", "CHAPTER01éAME=Intro"] {
+            match parse_chapters(text) {
+                Err(SublerError::InvalidChapters(_)) => {}
+                other => panic!("{:?}: {:?}", text, other),
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_non_ascii_titles() {
+        let chapters = parse_chapters("CHAPTER01=00:00:05\nCHAPTER01NAME=Résumé").unwrap();
+        assert_eq!(
+            chapters,
+            vec![Chapter::new(Duration::from_secs(5), "Résumé")]
+        );
+        let chapters = parse_chapters("00:05 Über").unwrap();
+        assert_eq!(chapters, vec![Chapter::new(Duration::from_secs(5), "Über")]);
+    }
+}
@@ -0,0 +1,365 @@
+//! Release dates in the ISO 8601 form iTunes sorts by
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{AtomKind, AtomValue, Atoms, Result, SublerError};
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+/// The value of the `Release Date` tag: a year, a day or a point in time
+///
+/// Renders as `2018`, `2018-03-01` or `2018-03-01T08:00:00Z`. Parsing accepts ISO 8601 with
+/// `-`, `/` or `.` separators, `DD.MM.YYYY`, month names and US or European slashed dates.
+/// Times with an offset are converted to UTC. Slashed dates that read as a valid day both
+/// ways, and two digit years, are rejected as ambiguous:
+///
+/// ```rust
+/// use sublercli::ReleaseDate;
+/// let parse = |date: &str| date.parse::<ReleaseDate>().map(|date| date.to_string());
+/// assert_eq!(parse("2018").unwrap(), "2018");
+/// assert_eq!(parse("2018/3/1").unwrap(), "2018-03-01");
+/// assert_eq!(parse("01.03.2018").unwrap(), "2018-03-01");
+/// assert_eq!(parse("March 1, 2018").unwrap(), "2018-03-01");
+/// assert_eq!(parse("1 Mar 2018").unwrap(), "2018-03-01");
+/// assert_eq!(parse("03/13/2018").unwrap(), "2018-03-13");
+/// assert_eq!(parse("2018-03-01 10:30:00+02:00").unwrap(), "2018-03-01T08:30:00Z");
+/// assert_eq!(parse("2018-03-01T01:00:00-02:00").unwrap(), "2018-03-01T03:00:00Z");
+///
+/// assert!(parse("03/01/2018").is_err());
+/// assert!(parse("03/01/18").is_err());
+/// assert!(parse("2018-02-30").is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReleaseDate {
+    year: u16,
+    /// Month and day
+    date: Option<(u8, u8)>,
+    /// Hour, minute and second in UTC
+    time: Option<(u8, u8, u8)>,
+}
+
+impl ReleaseDate {
+    /// Only the year
+    pub fn from_year(year: u16) -> ReleaseDate {
+        ReleaseDate {
+            year,
+            date: None,
+            time: None,
+        }
+    }
+
+    /// A day, fails with `SublerError::InvalidDate` if it doesn't exist
+    pub fn from_ymd(year: u16, month: u8, day: u8) -> Result<ReleaseDate> {
+        if month == 0 || month > 12 || day == 0 || day > days_in_month(year, month) {
+            return Err(SublerError::InvalidDate(format!(
+                "{:04}-{:02}-{:02}",
+                year, month, day
+            )));
+        }
+        Ok(ReleaseDate {
+            year,
+            date: Some((month, day)),
+            time: None,
+        })
+    }
+
+    /// A point in time in UTC, fails with `SublerError::InvalidDate` if it doesn't exist
+    pub fn from_ymd_hms(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<ReleaseDate> {
+        let date = ReleaseDate::from_ymd(year, month, day)?;
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(SublerError::InvalidDate(format!(
+                "{}T{:02}:{:02}:{:02}Z",
+                date, hour, minute, second
+            )));
+        }
+        Ok(ReleaseDate {
+            time: Some((hour, minute, second)),
+            ..date
+        })
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> Option<u8> {
+        self.date.map(|(month, _)| month)
+    }
+
+    pub fn day(&self) -> Option<u8> {
+        self.date.map(|(_, day)| day)
+    }
+
+    /// Hour, minute and second in UTC
+    pub fn time(&self) -> Option<(u8, u8, u8)> {
+        self.time
+    }
+}
+
+impl fmt::Display for ReleaseDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some((month, day)) = self.date {
+            write!(f, "-{:02}-{:02}", month, day)?;
+        }
+        if let Some((hour, minute, second)) = self.time {
+            write!(f, "T{:02}:{:02}:{:02}Z", hour, minute, second)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ReleaseDate {
+    type Err = SublerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        parse_iso(s)
+            .or_else(|| parse_numeric(s))
+            .or_else(|| parse_named(s))
+            .ok_or_else(|| SublerError::InvalidDate(s.to_owned()))
+    }
+}
+
+impl From<u16> for ReleaseDate {
+    fn from(year: u16) -> Self {
+        ReleaseDate::from_year(year)
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses a number of exactly `digits` ASCII digits
+fn number<T: FromStr>(text: &str, digits: usize) -> Option<T> {
+    if text.len() == digits && text.bytes().all(|b| b.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parses a day or month of one or two digits
+fn small(text: &str) -> Option<u8> {
+    number(text, 1).or_else(|| number(text, 2))
+}
+
+/// `YYYY`, `YYYYMMDD` and `YYYY-MM-DD` with `-`, `/` or `.`, optionally followed by a time
+fn parse_iso(s: &str) -> Option<ReleaseDate> {
+    if let Some(year) = number(s, 4) {
+        return Some(ReleaseDate::from_year(year));
+    }
+    if s.len() == 8 && s.bytes().all(|b| b.is_ascii_digit()) {
+        return ReleaseDate::from_ymd(
+            s[..4].parse().ok()?,
+            s[4..6].parse().ok()?,
+            s[6..].parse().ok()?,
+        )
+        .ok();
+    }
+    let (date, time) = match s.find(['T', ' ']) {
+        Some(pos) => (&s[..pos], Some(s[pos + 1..].trim())),
+        None => (s, None),
+    };
+    let separator = date
+        .chars()
+        .nth(4)
+        .filter(|c| ['-', '/', '.'].contains(c))?;
+    let parts: Vec<&str> = date.split(separator).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let (year, month, day) = (number(parts[0], 4)?, small(parts[1])?, small(parts[2])?);
+    match time {
+        None => ReleaseDate::from_ymd(year, month, day).ok(),
+        Some(time) => parse_time(year, month, day, time),
+    }
+}
+
+/// `HH:MM[:SS[.fff]]` with an optional `Z` or `±HH:MM` offset, converted to UTC
+fn parse_time(year: u16, month: u8, day: u8, time: &str) -> Option<ReleaseDate> {
+    ReleaseDate::from_ymd(year, month, day).ok()?;
+    let (clock, offset) = match time.find(['Z', 'z', '+', '-']) {
+        Some(pos) => (&time[..pos], parse_offset(&time[pos..])?),
+        None => (time, 0),
+    };
+    let clock = clock.split('.').next()?;
+    let parts: Vec<&str> = clock.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let hour: i64 = number(parts[0], 2)?;
+    let minute: i64 = number(parts[1], 2)?;
+    let second: i64 = parts.get(2).map_or(Some(0), |second| number(second, 2))?;
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let seconds = days_from_civil(i64::from(year), i64::from(month), i64::from(day)) * 86400
+        + hour * 3600
+        + minute * 60
+        + second
+        - offset;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let seconds = seconds.rem_euclid(86400);
+    ReleaseDate::from_ymd_hms(
+        u16::try_from(year).ok()?,
+        month as u8,
+        day as u8,
+        (seconds / 3600) as u8,
+        (seconds % 3600 / 60) as u8,
+        (seconds % 60) as u8,
+    )
+    .ok()
+}
+
+/// `Z`, `+HH:MM`, `+HHMM` or `+HH` as seconds east of UTC
+fn parse_offset(offset: &str) -> Option<i64> {
+    if offset.eq_ignore_ascii_case("z") {
+        return Some(0);
+    }
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let digits = offset[1..].replace(':', "");
+    let (hours, minutes): (i64, i64) = match digits.len() {
+        2 => (number(&digits, 2)?, 0),
+        4 => (number(&digits[..2], 2)?, number(&digits[2..], 2)?),
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+/// The days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date of a number of days since 1970-01-01, the inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// `DD.MM.YYYY`, and `MM/DD/YYYY` or `DD/MM/YYYY` when only one reading is a valid date
+fn parse_numeric(s: &str) -> Option<ReleaseDate> {
+    let separator = s.chars().find(|c| ['.', '/', '-'].contains(c))?;
+    let parts: Vec<&str> = s.split(separator).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let (first, second, year) = (small(parts[0])?, small(parts[1])?, number(parts[2], 4)?);
+    if separator == '.' {
+        return ReleaseDate::from_ymd(year, second, first).ok();
+    }
+    let day_first = ReleaseDate::from_ymd(year, second, first).ok();
+    let month_first = ReleaseDate::from_ymd(year, first, second).ok();
+    match (day_first, month_first) {
+        (Some(a), Some(b)) if a != b => None,
+        (Some(date), _) => Some(date),
+        (None, date) => date,
+    }
+}
+
+/// A day, an English month name or its first three letters and a year, in any order
+fn parse_named(s: &str) -> Option<ReleaseDate> {
+    let tokens: Vec<&str> = s
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+        .collect();
+    if tokens.len() != 3 {
+        return None;
+    }
+    let mut month = None;
+    let mut year = None;
+    let mut day = None;
+    for token in tokens {
+        let lower = token.trim_end_matches('.').to_lowercase();
+        if let Some(index) = MONTHS
+            .iter()
+            .position(|name| *name == lower || (lower.len() == 3 && name.starts_with(&lower)))
+        {
+            month = Some(index as u8 + 1);
+        } else if let Some(number) = number(token, 4) {
+            year = Some(number);
+        } else {
+            day = Some(small(token)?);
+        }
+    }
+    ReleaseDate::from_ymd(year?, month?, day?).ok()
+}
+
+impl Atoms {
+    /// Parses `Release Date` atoms set as text into a `ReleaseDate`, so they're written in
+    /// ISO 8601 form
+    ///
+    /// Fails with `SublerError::InvalidDate` if a date can't be parsed or is ambiguous.
+    ///
+    /// ```rust
+    /// use sublercli::*;
+    /// let mut atoms = Atoms::new().add("Release Date", "March 1, 2018").build();
+    /// atoms.normalize_dates().unwrap();
+    /// assert_eq!(atoms.args().unwrap()[1], "{Release Date:2018-03-01}");
+    /// ```
+    pub fn normalize_dates(&mut self) -> Result<&mut Self> {
+        for atom in self.atoms_mut() {
+            if atom.is_deletion() || Atoms::tag_kind(&atom.tag) != Some(AtomKind::Date) {
+                continue;
+            }
+            if let AtomValue::Text(text) = &atom.value {
+                atom.value = AtomValue::Date(text.parse()?);
+            }
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,189 @@
+//! The error type returned by all fallible operations of this crate
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use crate::AtomProblem;
+
+/// Result type used throughout the crate
+pub type Result<T, E = SublerError> = ::std::result::Result<T, E>;
+
+/// Everything that can go wrong while preparing or running a tagging process
+#[derive(Debug)]
+pub enum SublerError {
+    /// The source file does not exist
+    SourceNotFound(PathBuf),
+    /// The artwork file does not exist
+    ArtworkNotFound(PathBuf),
+    /// The subtitle file does not exist
+    SubtitleNotFound(PathBuf),
+    /// No usable destination path could be derived from the given path
+    InvalidDestination(PathBuf),
+    /// The SublerCli executable could not be found at the given path
+    ExecutableNotFound(PathBuf),
+    /// SublerCli ran, but exited with a non-zero status
+    CliFailed {
+        /// The exit status of the process
+        status: ExitStatus,
+        /// The captured stderr of the process
+        stderr: String,
+    },
+    /// A file could not be parsed as MP4
+    InvalidMp4(String),
+    /// The value of an atom can't be stored for its tag
+    InvalidAtomValue {
+        /// The tag of the atom
+        tag: String,
+        /// The rejected value
+        value: String,
+    },
+    /// The atom can't be passed to SublerCli without being mis-parsed, because its value
+    /// contains `{` or `}`, or its tag is empty or contains `{`, `}` or `:`
+    AmbiguousAtom {
+        /// The tag of the atom
+        tag: String,
+        /// The value of the atom
+        value: String,
+    },
+    /// `Atoms::validate` found problems with the atoms, all of them are listed
+    InvalidAtoms(Vec<AtomProblem>),
+    /// A line of a chapter file could not be parsed
+    InvalidChapters(String),
+    /// The language is not a known ISO 639 language, or not known to SublerCli
+    UnknownLanguage(String),
+    /// The artwork is not a JPEG, PNG or BMP image
+    InvalidArtwork(String),
+    /// The release date can't be parsed, doesn't exist or is ambiguous, like `03/01/2018`
+    InvalidDate(String),
+    /// The content rating is not known, or its code doesn't match its label
+    UnknownRating(String),
+    /// The backend does not support a requested feature
+    Unsupported(&'static str),
+    /// The tagging run took longer than the configured timeout and was stopped
+    Timeout(Duration),
+    /// The tagging run was stopped with a `CancelHandle`
+    Cancelled,
+    /// Any other io error
+    Io(io::Error),
+}
+
+impl SublerError {
+    /// The `io::ErrorKind` that best describes this error
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            SublerError::SourceNotFound(_)
+            | SublerError::ArtworkNotFound(_)
+            | SublerError::SubtitleNotFound(_)
+            | SublerError::ExecutableNotFound(_) => io::ErrorKind::NotFound,
+            SublerError::InvalidDestination(_) => io::ErrorKind::InvalidInput,
+            SublerError::InvalidMp4(_) | SublerError::InvalidArtwork(_) => {
+                io::ErrorKind::InvalidData
+            }
+            SublerError::InvalidAtomValue { .. }
+            | SublerError::AmbiguousAtom { .. }
+            | SublerError::InvalidAtoms(_) => io::ErrorKind::InvalidInput,
+            SublerError::InvalidChapters(_) => io::ErrorKind::InvalidData,
+            SublerError::InvalidDate(_)
+            | SublerError::UnknownLanguage(_)
+            | SublerError::UnknownRating(_) => io::ErrorKind::InvalidInput,
+            SublerError::CliFailed { .. } | SublerError::Unsupported(_) => io::ErrorKind::Other,
+            SublerError::Timeout(_) => io::ErrorKind::TimedOut,
+            SublerError::Cancelled => io::ErrorKind::Interrupted,
+            SublerError::Io(err) => err.kind(),
+        }
+    }
+}
+
+impl fmt::Display for SublerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SublerError::SourceNotFound(path) => {
+                write!(f, "Source file does not exist: {}", path.display())
+            }
+            SublerError::ArtworkNotFound(path) => {
+                write!(f, "Artwork file does not exist: {}", path.display())
+            }
+            SublerError::SubtitleNotFound(path) => {
+                write!(f, "Subtitle file does not exist: {}", path.display())
+            }
+            SublerError::InvalidDestination(path) => {
+                write!(f, "No usable destination for: {}", path.display())
+            }
+            SublerError::ExecutableNotFound(path) => {
+                write!(f, "SublerCli executable not found at: {}", path.display())
+            }
+            SublerError::CliFailed { status, stderr } => {
+                write!(f, "SublerCli failed with {}", status)?;
+                if !stderr.trim().is_empty() {
+                    write!(f, ": {}", stderr.trim())?;
+                }
+                Ok(())
+            }
+            SublerError::InvalidMp4(msg) => write!(f, "Invalid MP4 file: {}", msg),
+            SublerError::InvalidAtomValue { tag, value } => {
+                write!(f, "Invalid value for atom {}: {:?}", tag, value)
+            }
+            SublerError::AmbiguousAtom { tag, value } => write!(
+                f,
+                "Atom {:?} with value {:?} can't be passed to SublerCli: \
+                 tags must not contain `{{`, `}}` or `:` and values must not contain `{{` or `}}`",
+                tag, value
+            ),
+            SublerError::InvalidAtoms(problems) => {
+                write!(f, "Invalid atoms: ")?;
+                for (index, problem) in problems.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", problem)?;
+                }
+                Ok(())
+            }
+            SublerError::InvalidChapters(line) => write!(f, "Invalid chapter line: {:?}", line),
+            SublerError::UnknownLanguage(language) => write!(f, "Unknown language: {}", language),
+            SublerError::InvalidArtwork(artwork) => {
+                write!(f, "Artwork is not a JPEG, PNG or BMP image: {}", artwork)
+            }
+            SublerError::InvalidDate(date) => {
+                write!(f, "Invalid or ambiguous release date: {}", date)
+            }
+            SublerError::UnknownRating(rating) => write!(f, "Unknown content rating: {}", rating),
+            SublerError::Unsupported(feature) => {
+                write!(f, "{} is not supported by this backend", feature)
+            }
+            SublerError::Timeout(timeout) => {
+                write!(f, "Tagging timed out after {:?}", timeout)
+            }
+            SublerError::Cancelled => write!(f, "Tagging was cancelled"),
+            SublerError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for SublerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SublerError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SublerError {
+    fn from(err: io::Error) -> Self {
+        SublerError::Io(err)
+    }
+}
+
+impl From<SublerError> for io::Error {
+    fn from(err: SublerError) -> Self {
+        match err {
+            SublerError::Io(err) => err,
+            err => io::Error::new(err.kind(), err),
+        }
+    }
+}
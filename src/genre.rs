@@ -0,0 +1,227 @@
+//! The iTunes Store genres and their `genreID`s, built in so lookups work offline
+
+use crate::{Atom, AtomValue, Atoms, MediaKind};
+
+/// The store catalog a genre belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenreKind {
+    Movie,
+    TvShow,
+    Music,
+}
+
+impl GenreKind {
+    /// The catalog of files of the media kind, if the store has one
+    pub fn for_media_kind(kind: MediaKind) -> Option<GenreKind> {
+        match kind {
+            MediaKind::Movie => Some(GenreKind::Movie),
+            MediaKind::TVShow => Some(GenreKind::TvShow),
+            MediaKind::Music | MediaKind::MusicVideo => Some(GenreKind::Music),
+            _ => None,
+        }
+    }
+}
+
+/// All genres: catalog, name and the iTunes Store genre ID
+const GENRES: &[(GenreKind, &str, u32)] = &[
+    (GenreKind::Movie, "Action & Adventure", 4401),
+    (GenreKind::Movie, "Anime", 4402),
+    (GenreKind::Movie, "Classics", 4403),
+    (GenreKind::Movie, "Comedy", 4404),
+    (GenreKind::Movie, "Documentary", 4405),
+    (GenreKind::Movie, "Drama", 4406),
+    (GenreKind::Movie, "Foreign", 4407),
+    (GenreKind::Movie, "Horror", 4408),
+    (GenreKind::Movie, "Independent", 4409),
+    (GenreKind::Movie, "Kids & Family", 4410),
+    (GenreKind::Movie, "Musicals", 4411),
+    (GenreKind::Movie, "Romance", 4412),
+    (GenreKind::Movie, "Sci-Fi & Fantasy", 4413),
+    (GenreKind::Movie, "Short Films", 4414),
+    (GenreKind::Movie, "Special Interest", 4415),
+    (GenreKind::Movie, "Thriller", 4416),
+    (GenreKind::Movie, "Sports", 4417),
+    (GenreKind::Movie, "Western", 4418),
+    (GenreKind::Movie, "Urban", 4419),
+    (GenreKind::Movie, "Holiday", 4420),
+    (GenreKind::Movie, "Made for TV", 4421),
+    (GenreKind::Movie, "Concert Films", 4422),
+    (GenreKind::Movie, "Music Documentaries", 4423),
+    (GenreKind::Movie, "Music Feature Films", 4424),
+    (GenreKind::Movie, "Japanese Cinema", 4425),
+    (GenreKind::Movie, "Jidaigeki", 4426),
+    (GenreKind::Movie, "Tokusatsu", 4427),
+    (GenreKind::Movie, "Korean Cinema", 4428),
+    (GenreKind::TvShow, "Comedy", 4000),
+    (GenreKind::TvShow, "Drama", 4001),
+    (GenreKind::TvShow, "Animation", 4002),
+    (GenreKind::TvShow, "Action & Adventure", 4003),
+    (GenreKind::TvShow, "Classic", 4004),
+    (GenreKind::TvShow, "Kids", 4005),
+    (GenreKind::TvShow, "Nonfiction", 4006),
+    (GenreKind::TvShow, "Reality TV", 4007),
+    (GenreKind::TvShow, "Sci-Fi & Fantasy", 4008),
+    (GenreKind::TvShow, "Sports", 4009),
+    (GenreKind::TvShow, "Teens", 4010),
+    (GenreKind::TvShow, "Latino TV", 4011),
+    (GenreKind::Music, "Blues", 2),
+    (GenreKind::Music, "Comedy", 3),
+    (GenreKind::Music, "Children's Music", 4),
+    (GenreKind::Music, "Classical", 5),
+    (GenreKind::Music, "Country", 6),
+    (GenreKind::Music, "Electronic", 7),
+    (GenreKind::Music, "Holiday", 8),
+    (GenreKind::Music, "Opera", 9),
+    (GenreKind::Music, "Singer/Songwriter", 10),
+    (GenreKind::Music, "Jazz", 11),
+    (GenreKind::Music, "Latino", 12),
+    (GenreKind::Music, "New Age", 13),
+    (GenreKind::Music, "Pop", 14),
+    (GenreKind::Music, "R&B/Soul", 15),
+    (GenreKind::Music, "Soundtrack", 16),
+    (GenreKind::Music, "Dance", 17),
+    (GenreKind::Music, "Hip-Hop/Rap", 18),
+    (GenreKind::Music, "World", 19),
+    (GenreKind::Music, "Alternative", 20),
+    (GenreKind::Music, "Rock", 21),
+    (GenreKind::Music, "Christian & Gospel", 22),
+    (GenreKind::Music, "Vocal", 23),
+    (GenreKind::Music, "Reggae", 24),
+    (GenreKind::Music, "Easy Listening", 25),
+    (GenreKind::Music, "J-Pop", 27),
+    (GenreKind::Music, "Enka", 28),
+    (GenreKind::Music, "Anime", 29),
+    (GenreKind::Music, "Kayokyoku", 30),
+    (GenreKind::Music, "Fitness & Workout", 50),
+    (GenreKind::Music, "K-Pop", 51),
+    (GenreKind::Music, "Karaoke", 52),
+    (GenreKind::Music, "Instrumental", 53),
+];
+
+/// Common alternative names: catalog, alias and the name of the genre in `GENRES`
+const ALIASES: &[(GenreKind, &str, &str)] = &[
+    (GenreKind::Movie, "Action", "Action & Adventure"),
+    (GenreKind::Movie, "Adventure", "Action & Adventure"),
+    (GenreKind::Movie, "Sci-Fi", "Sci-Fi & Fantasy"),
+    (GenreKind::Movie, "Science Fiction", "Sci-Fi & Fantasy"),
+    (GenreKind::Movie, "Fantasy", "Sci-Fi & Fantasy"),
+    (GenreKind::Movie, "Kids", "Kids & Family"),
+    (GenreKind::Movie, "Family", "Kids & Family"),
+    (GenreKind::Movie, "Children", "Kids & Family"),
+    (GenreKind::Movie, "Documentaries", "Documentary"),
+    (GenreKind::Movie, "Musical", "Musicals"),
+    (GenreKind::Movie, "Classic", "Classics"),
+    (GenreKind::Movie, "Indie", "Independent"),
+    (GenreKind::Movie, "Suspense", "Thriller"),
+    (GenreKind::TvShow, "Action", "Action & Adventure"),
+    (GenreKind::TvShow, "Adventure", "Action & Adventure"),
+    (GenreKind::TvShow, "Sci-Fi", "Sci-Fi & Fantasy"),
+    (GenreKind::TvShow, "Science Fiction", "Sci-Fi & Fantasy"),
+    (GenreKind::TvShow, "Fantasy", "Sci-Fi & Fantasy"),
+    (GenreKind::TvShow, "Reality", "Reality TV"),
+    (GenreKind::TvShow, "Documentary", "Nonfiction"),
+    (GenreKind::TvShow, "Animated", "Animation"),
+    (GenreKind::TvShow, "Family", "Kids"),
+    (GenreKind::Music, "Hip-Hop", "Hip-Hop/Rap"),
+    (GenreKind::Music, "Hip Hop", "Hip-Hop/Rap"),
+    (GenreKind::Music, "Rap", "Hip-Hop/Rap"),
+    (GenreKind::Music, "R&B", "R&B/Soul"),
+    (GenreKind::Music, "RnB", "R&B/Soul"),
+    (GenreKind::Music, "Soul", "R&B/Soul"),
+    (GenreKind::Music, "Electronica", "Electronic"),
+    (GenreKind::Music, "Christian", "Christian & Gospel"),
+    (GenreKind::Music, "Gospel", "Christian & Gospel"),
+    (GenreKind::Music, "Children's", "Children's Music"),
+    (GenreKind::Music, "Kids", "Children's Music"),
+    (GenreKind::Music, "Latin", "Latino"),
+    (GenreKind::Music, "Singer-Songwriter", "Singer/Songwriter"),
+    (GenreKind::Music, "Score", "Soundtrack"),
+    (GenreKind::Music, "Workout", "Fitness & Workout"),
+];
+
+/// A genre of the iTunes Store catalog
+///
+/// Names and aliases are matched ignoring case:
+///
+/// ```rust
+/// use sublercli::{Genre, GenreKind};
+/// let genre = Genre::lookup(GenreKind::Movie, "sci-fi").unwrap();
+/// assert_eq!(genre.name(), "Sci-Fi & Fantasy");
+/// assert_eq!(genre.id(), 4413);
+///
+/// // the same name has a different ID in every catalog
+/// assert_eq!(Genre::lookup(GenreKind::TvShow, "Comedy").unwrap().id(), 4000);
+/// assert_eq!(Genre::lookup(GenreKind::Music, "Comedy").unwrap().id(), 3);
+/// assert_eq!(Genre::lookup(GenreKind::Music, "Foo Bar"), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Genre {
+    kind: GenreKind,
+    name: &'static str,
+    id: u32,
+}
+
+impl Genre {
+    /// The genre of the catalog with the name or alias
+    pub fn lookup(kind: GenreKind, name: &str) -> Option<Genre> {
+        let name = name.trim();
+        let name = ALIASES
+            .iter()
+            .find(|(k, alias, _)| *k == kind && alias.eq_ignore_ascii_case(name))
+            .map_or(name, |(_, _, genre)| *genre);
+        Genre::all().find(|genre| genre.kind == kind && genre.name.eq_ignore_ascii_case(name))
+    }
+
+    /// All genres of all catalogs
+    pub fn all() -> impl Iterator<Item = Genre> {
+        GENRES
+            .iter()
+            .map(|&(kind, name, id)| Genre { kind, name, id })
+    }
+
+    pub fn kind(&self) -> GenreKind {
+        self.kind
+    }
+
+    /// The name of the genre in the store
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The value of the `genreID` atom
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Atoms {
+    /// Sets `genreID` from the `Genre` atom, if the genre is known in the catalog
+    ///
+    /// Of a comma separated list of genres the first known one is used, an existing
+    /// `genreID` is kept. Returns the genre the ID was taken from.
+    ///
+    /// ```rust
+    /// use sublercli::*;
+    /// let mut atoms = Atoms::new().genre("Indie, Sci-Fi").build();
+    /// let genre = atoms.fill_genre_id(GenreKind::Movie).unwrap();
+    /// assert_eq!(genre.name(), "Independent");
+    /// assert_eq!(atoms.get("genreID").unwrap().value, AtomValue::Integer(4409));
+    /// ```
+    pub fn fill_genre_id(&mut self, kind: GenreKind) -> Option<Genre> {
+        if self.contains("genreID") {
+            return None;
+        }
+        let genre = self
+            .get("Genre")
+            .filter(|atom| !atom.is_deletion())?
+            .value
+            .to_string()
+            .split(',')
+            .find_map(|name| Genre::lookup(kind, name))?;
+        self.add_atom(Atom::with_value(
+            "genreID",
+            AtomValue::Integer(i64::from(genre.id())),
+        ));
+        Some(genre)
+    }
+}
@@ -0,0 +1,292 @@
+//! ISO 639 languages for subtitle and audio tracks
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{Result, SublerCli, SublerError};
+
+/// `(ISO 639-1, ISO 639-2/B, ISO 639-2/T, English name)` of every known language
+const LANGUAGES: &[(Option<&str>, &str, &str, &str)] = &[
+    (Some("aa"), "aar", "aar", "Afar"),
+    (Some("ab"), "abk", "abk", "Abkhazian"),
+    (Some("ae"), "ave", "ave", "Avestan"),
+    (Some("af"), "afr", "afr", "Afrikaans"),
+    (Some("ak"), "aka", "aka", "Akan"),
+    (Some("am"), "amh", "amh", "Amharic"),
+    (Some("an"), "arg", "arg", "Aragonese"),
+    (Some("ar"), "ara", "ara", "Arabic"),
+    (Some("as"), "asm", "asm", "Assamese"),
+    (Some("av"), "ava", "ava", "Avaric"),
+    (Some("ay"), "aym", "aym", "Aymara"),
+    (Some("az"), "aze", "aze", "Azerbaijani"),
+    (Some("ba"), "bak", "bak", "Bashkir"),
+    (Some("be"), "bel", "bel", "Belarusian"),
+    (Some("bg"), "bul", "bul", "Bulgarian"),
+    (Some("bi"), "bis", "bis", "Bislama"),
+    (Some("bm"), "bam", "bam", "Bambara"),
+    (Some("bn"), "ben", "ben", "Bengali"),
+    (Some("bo"), "tib", "bod", "Tibetan"),
+    (Some("br"), "bre", "bre", "Breton"),
+    (Some("bs"), "bos", "bos", "Bosnian"),
+    (Some("ca"), "cat", "cat", "Catalan"),
+    (Some("ce"), "che", "che", "Chechen"),
+    (Some("ch"), "cha", "cha", "Chamorro"),
+    (Some("co"), "cos", "cos", "Corsican"),
+    (Some("cr"), "cre", "cre", "Cree"),
+    (Some("cs"), "cze", "ces", "Czech"),
+    (Some("cu"), "chu", "chu", "Church Slavic"),
+    (Some("cv"), "chv", "chv", "Chuvash"),
+    (Some("cy"), "wel", "cym", "Welsh"),
+    (Some("da"), "dan", "dan", "Danish"),
+    (Some("de"), "ger", "deu", "German"),
+    (Some("dv"), "div", "div", "Divehi"),
+    (Some("dz"), "dzo", "dzo", "Dzongkha"),
+    (Some("ee"), "ewe", "ewe", "Ewe"),
+    (Some("el"), "gre", "ell", "Greek"),
+    (Some("en"), "eng", "eng", "English"),
+    (Some("eo"), "epo", "epo", "Esperanto"),
+    (Some("es"), "spa", "spa", "Spanish"),
+    (Some("et"), "est", "est", "Estonian"),
+    (Some("eu"), "baq", "eus", "Basque"),
+    (Some("fa"), "per", "fas", "Persian"),
+    (Some("ff"), "ful", "ful", "Fulah"),
+    (Some("fi"), "fin", "fin", "Finnish"),
+    (Some("fj"), "fij", "fij", "Fijian"),
+    (Some("fo"), "fao", "fao", "Faroese"),
+    (Some("fr"), "fre", "fra", "French"),
+    (Some("fy"), "fry", "fry", "Western Frisian"),
+    (Some("ga"), "gle", "gle", "Irish"),
+    (Some("gd"), "gla", "gla", "Scottish Gaelic"),
+    (Some("gl"), "glg", "glg", "Galician"),
+    (Some("gn"), "grn", "grn", "Guarani"),
+    (Some("gu"), "guj", "guj", "Gujarati"),
+    (Some("gv"), "glv", "glv", "Manx"),
+    (Some("ha"), "hau", "hau", "Hausa"),
+    (Some("he"), "heb", "heb", "Hebrew"),
+    (Some("hi"), "hin", "hin", "Hindi"),
+    (Some("ho"), "hmo", "hmo", "Hiri Motu"),
+    (Some("hr"), "hrv", "hrv", "Croatian"),
+    (Some("ht"), "hat", "hat", "Haitian"),
+    (Some("hu"), "hun", "hun", "Hungarian"),
+    (Some("hy"), "arm", "hye", "Armenian"),
+    (Some("hz"), "her", "her", "Herero"),
+    (Some("ia"), "ina", "ina", "Interlingua"),
+    (Some("id"), "ind", "ind", "Indonesian"),
+    (Some("ie"), "ile", "ile", "Interlingue"),
+    (Some("ig"), "ibo", "ibo", "Igbo"),
+    (Some("ii"), "iii", "iii", "Sichuan Yi"),
+    (Some("ik"), "ipk", "ipk", "Inupiaq"),
+    (Some("io"), "ido", "ido", "Ido"),
+    (Some("is"), "ice", "isl", "Icelandic"),
+    (Some("it"), "ita", "ita", "Italian"),
+    (Some("iu"), "iku", "iku", "Inuktitut"),
+    (Some("ja"), "jpn", "jpn", "Japanese"),
+    (Some("jv"), "jav", "jav", "Javanese"),
+    (Some("ka"), "geo", "kat", "Georgian"),
+    (Some("kg"), "kon", "kon", "Kongo"),
+    (Some("ki"), "kik", "kik", "Kikuyu"),
+    (Some("kj"), "kua", "kua", "Kuanyama"),
+    (Some("kk"), "kaz", "kaz", "Kazakh"),
+    (Some("kl"), "kal", "kal", "Kalaallisut"),
+    (Some("km"), "khm", "khm", "Central Khmer"),
+    (Some("kn"), "kan", "kan", "Kannada"),
+    (Some("ko"), "kor", "kor", "Korean"),
+    (Some("kr"), "kau", "kau", "Kanuri"),
+    (Some("ks"), "kas", "kas", "Kashmiri"),
+    (Some("ku"), "kur", "kur", "Kurdish"),
+    (Some("kv"), "kom", "kom", "Komi"),
+    (Some("kw"), "cor", "cor", "Cornish"),
+    (Some("ky"), "kir", "kir", "Kirghiz"),
+    (Some("la"), "lat", "lat", "Latin"),
+    (Some("lb"), "ltz", "ltz", "Luxembourgish"),
+    (Some("lg"), "lug", "lug", "Ganda"),
+    (Some("li"), "lim", "lim", "Limburgish"),
+    (Some("ln"), "lin", "lin", "Lingala"),
+    (Some("lo"), "lao", "lao", "Lao"),
+    (Some("lt"), "lit", "lit", "Lithuanian"),
+    (Some("lu"), "lub", "lub", "Luba-Katanga"),
+    (Some("lv"), "lav", "lav", "Latvian"),
+    (Some("mg"), "mlg", "mlg", "Malagasy"),
+    (Some("mh"), "mah", "mah", "Marshallese"),
+    (Some("mi"), "mao", "mri", "Maori"),
+    (Some("mk"), "mac", "mkd", "Macedonian"),
+    (Some("ml"), "mal", "mal", "Malayalam"),
+    (Some("mn"), "mon", "mon", "Mongolian"),
+    (Some("mr"), "mar", "mar", "Marathi"),
+    (Some("ms"), "may", "msa", "Malay"),
+    (Some("mt"), "mlt", "mlt", "Maltese"),
+    (Some("my"), "bur", "mya", "Burmese"),
+    (Some("na"), "nau", "nau", "Nauru"),
+    (Some("nb"), "nob", "nob", "Norwegian Bokmål"),
+    (Some("nd"), "nde", "nde", "North Ndebele"),
+    (Some("ne"), "nep", "nep", "Nepali"),
+    (Some("ng"), "ndo", "ndo", "Ndonga"),
+    (Some("nl"), "dut", "nld", "Dutch"),
+    (Some("nn"), "nno", "nno", "Norwegian Nynorsk"),
+    (Some("no"), "nor", "nor", "Norwegian"),
+    (Some("nr"), "nbl", "nbl", "South Ndebele"),
+    (Some("nv"), "nav", "nav", "Navajo"),
+    (Some("ny"), "nya", "nya", "Chichewa"),
+    (Some("oc"), "oci", "oci", "Occitan"),
+    (Some("oj"), "oji", "oji", "Ojibwa"),
+    (Some("om"), "orm", "orm", "Oromo"),
+    (Some("or"), "ori", "ori", "Oriya"),
+    (Some("os"), "oss", "oss", "Ossetian"),
+    (Some("pa"), "pan", "pan", "Punjabi"),
+    (Some("pi"), "pli", "pli", "Pali"),
+    (Some("pl"), "pol", "pol", "Polish"),
+    (Some("ps"), "pus", "pus", "Pashto"),
+    (Some("pt"), "por", "por", "Portuguese"),
+    (Some("qu"), "que", "que", "Quechua"),
+    (Some("rm"), "roh", "roh", "Romansh"),
+    (Some("rn"), "run", "run", "Rundi"),
+    (Some("ro"), "rum", "ron", "Romanian"),
+    (Some("ru"), "rus", "rus", "Russian"),
+    (Some("rw"), "kin", "kin", "Kinyarwanda"),
+    (Some("sa"), "san", "san", "Sanskrit"),
+    (Some("sc"), "srd", "srd", "Sardinian"),
+    (Some("sd"), "snd", "snd", "Sindhi"),
+    (Some("se"), "sme", "sme", "Northern Sami"),
+    (Some("sg"), "sag", "sag", "Sango"),
+    (Some("si"), "sin", "sin", "Sinhala"),
+    (Some("sk"), "slo", "slk", "Slovak"),
+    (Some("sl"), "slv", "slv", "Slovenian"),
+    (Some("sm"), "smo", "smo", "Samoan"),
+    (Some("sn"), "sna", "sna", "Shona"),
+    (Some("so"), "som", "som", "Somali"),
+    (Some("sq"), "alb", "sqi", "Albanian"),
+    (Some("sr"), "srp", "srp", "Serbian"),
+    (Some("ss"), "ssw", "ssw", "Swati"),
+    (Some("st"), "sot", "sot", "Southern Sotho"),
+    (Some("su"), "sun", "sun", "Sundanese"),
+    (Some("sv"), "swe", "swe", "Swedish"),
+    (Some("sw"), "swa", "swa", "Swahili"),
+    (Some("ta"), "tam", "tam", "Tamil"),
+    (Some("te"), "tel", "tel", "Telugu"),
+    (Some("tg"), "tgk", "tgk", "Tajik"),
+    (Some("th"), "tha", "tha", "Thai"),
+    (Some("ti"), "tir", "tir", "Tigrinya"),
+    (Some("tk"), "tuk", "tuk", "Turkmen"),
+    (Some("tl"), "tgl", "tgl", "Tagalog"),
+    (Some("tn"), "tsn", "tsn", "Tswana"),
+    (Some("to"), "ton", "ton", "Tonga"),
+    (Some("tr"), "tur", "tur", "Turkish"),
+    (Some("ts"), "tso", "tso", "Tsonga"),
+    (Some("tt"), "tat", "tat", "Tatar"),
+    (Some("tw"), "twi", "twi", "Twi"),
+    (Some("ty"), "tah", "tah", "Tahitian"),
+    (Some("ug"), "uig", "uig", "Uighur"),
+    (Some("uk"), "ukr", "ukr", "Ukrainian"),
+    (Some("ur"), "urd", "urd", "Urdu"),
+    (Some("uz"), "uzb", "uzb", "Uzbek"),
+    (Some("ve"), "ven", "ven", "Venda"),
+    (Some("vi"), "vie", "vie", "Vietnamese"),
+    (Some("vo"), "vol", "vol", "Volapük"),
+    (Some("wa"), "wln", "wln", "Walloon"),
+    (Some("wo"), "wol", "wol", "Wolof"),
+    (Some("xh"), "xho", "xho", "Xhosa"),
+    (Some("yi"), "yid", "yid", "Yiddish"),
+    (Some("yo"), "yor", "yor", "Yoruba"),
+    (Some("za"), "zha", "zha", "Zhuang"),
+    (Some("zh"), "chi", "zho", "Chinese"),
+    (Some("zu"), "zul", "zul", "Zulu"),
+    (None, "mul", "mul", "Multiple languages"),
+    (None, "und", "und", "Undetermined"),
+];
+
+/// A language, identified by its ISO 639 codes
+///
+/// Parses ISO 639-1 and ISO 639-2 codes as well as English names, ignoring case,
+/// and renders as the English name SublerCli expects:
+///
+/// ```rust
+/// use sublercli::Language;
+/// let german: Language = "de".parse().unwrap();
+/// assert_eq!(german, "ger".parse().unwrap());
+/// assert_eq!(german, "deu".parse().unwrap());
+/// assert_eq!(german, "german".parse().unwrap());
+/// assert_eq!(german.to_string(), "German");
+/// assert_eq!(german.iso639_1(), Some("de"));
+/// assert!("Engish".parse::<Language>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Language {
+    iso639_1: Option<&'static str>,
+    iso639_2: &'static str,
+    iso639_2t: &'static str,
+    name: &'static str,
+}
+
+impl Language {
+    /// All known languages
+    pub fn all() -> Vec<Language> {
+        LANGUAGES
+            .iter()
+            .map(|&(iso639_1, iso639_2, iso639_2t, name)| Language {
+                iso639_1,
+                iso639_2,
+                iso639_2t,
+                name,
+            })
+            .collect()
+    }
+
+    /// The English name of the language, as used by SublerCli
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The two letter ISO 639-1 code, not every language has one
+    pub fn iso639_1(&self) -> Option<&'static str> {
+        self.iso639_1
+    }
+
+    /// The three letter bibliographic ISO 639-2/B code
+    pub fn iso639_2(&self) -> &'static str {
+        self.iso639_2
+    }
+
+    /// The three letter terminologic ISO 639-2/T code
+    pub fn iso639_2t(&self) -> &'static str {
+        self.iso639_2t
+    }
+
+    /// Whether the language is contained in a list of names as printed by `-listlanguages`
+    pub fn is_listed_in<S: AsRef<str>>(&self, languages: &[S]) -> bool {
+        languages
+            .iter()
+            .any(|l| l.as_ref().trim().eq_ignore_ascii_case(self.name))
+    }
+
+    /// Checks that SublerCli knows the language, using its `-listlanguages` output
+    pub fn check_with(&self, cli: &SublerCli) -> Result<()> {
+        if self.is_listed_in(&cli.list_languages()?) {
+            Ok(())
+        } else {
+            Err(SublerError::UnknownLanguage(self.name.to_owned()))
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = SublerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        Language::all()
+            .into_iter()
+            .find(|l| {
+                l.iso639_1.is_some_and(|c| c.eq_ignore_ascii_case(s))
+                    || l.iso639_2.eq_ignore_ascii_case(s)
+                    || l.iso639_2t.eq_ignore_ascii_case(s)
+                    || l.name.eq_ignore_ascii_case(s)
+            })
+            .ok_or_else(|| SublerError::UnknownLanguage(s.to_owned()))
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
@@ -32,7 +32,80 @@
 //!     .genre("Foo,Bar")
 //!     .artist("Foo Artist")
 //!     .title("Foo Bar Title")
-//!     .release_date("2018")
+//!     .release_date(2018)
+//!     .build();
+//! ```
+//!
+//! Every known tag declares the kind of value it holds, an `AtomKind`. Its builder method
+//! takes the matching Rust type and serializes it the way SublerCli expects:
+//!
+//! ```rust
+//! use sublercli::*;
+//! let atoms = Atoms::new()
+//!     .track_number(3, Some(12))
+//!     .hd_video(true)
+//!     .tempo(120)
+//!     .artwork("cover.jpg")
+//!     .build();
+//! assert_eq!(
+//!     atoms.args().unwrap()[1],
+//!     "{Track #:3/12}{HD Video:1}{Tempo:120}{Artwork:cover.jpg}"
+//! );
+//! assert_eq!(Atoms::tag_kind("Track #"), Some(AtomKind::Pair));
+//! ```
+//!
+//! Release dates are `ReleaseDate`s and written in ISO 8601 form. Dates set as text are
+//! parsed when tagging, ambiguous ones like `03/01/2018` fail with `SublerError::InvalidDate`.
+//!
+//! `Atoms` are keyed by tag name, setting a tag again replaces its value. `insert` takes
+//! an explicit `OnDuplicate` to append further values or to keep the first one instead:
+//!
+//! ```rust
+//! use sublercli::*;
+//! let atoms = Atoms::new()
+//!     .name("Foo")
+//!     .title("Bar")
+//!     .insert(Atom::new("Artwork", "front.jpg"), OnDuplicate::Append)
+//!     .insert(Atom::new("Artwork", "back.jpg"), OnDuplicate::Append)
+//!     .insert(Atom::new("name", "Baz"), OnDuplicate::KeepFirst)
+//!     .build();
+//! assert_eq!(
+//!     atoms.args().unwrap()[1],
+//!     "{Name:Bar}{Artwork:front.jpg}{Artwork:back.jpg}"
+//! );
+//! ```
+//!
+//! Covers are `Artwork`s, read from a file or held in memory. `artwork` replaces all covers,
+//! `add_artwork` adds another one. Relative paths are resolved against the directory of the
+//! source file, and every cover is checked to be a JPEG, PNG or BMP image before tagging:
+//!
+//! ```rust,no_run
+//! use sublercli::*;
+//! # let png_bytes = Vec::new();
+//! let atoms = Atoms::new()
+//!     .artwork("front.jpg")
+//!     .add_artwork(Artwork::from_bytes(png_bytes))
+//!     .build();
+//! ```
+//!
+//! Genres of the iTunes Store are built in, `Subler::auto_genre_id` fills in the matching
+//! `genreID` for the media kind of the file:
+//!
+//! ```rust
+//! use sublercli::*;
+//! let mut atoms = Atoms::new().genre("Science Fiction").build();
+//! atoms.fill_genre_id(GenreKind::TvShow);
+//! assert_eq!(atoms.args().unwrap()[1], "{Genre:Science Fiction}{genreID:4008}");
+//! ```
+//!
+//! Existing metadata can be removed from a file, either completely with
+//! `Subler::remove_existing` or for single tags:
+//!
+//! ```rust,no_run
+//! use sublercli::*;
+//! let atoms = Atoms::new()
+//!     .delete("Description")
+//!     .delete("Artwork")
 //!     .build();
 //! ```
 //!
@@ -58,16 +131,153 @@
 //!     // alternativly spawn the process: `.spawn_tag()`
 //!     .tag()
 //!
-//!     .and_then(|x| {
-//!         println!("stdout: {}", String::from_utf8_lossy(&x.stdout));
+//!     .and_then(|outcome| {
+//!         println!("tagged {} in {:?}", outcome.dest.display(), outcome.elapsed);
+//!         for warning in &outcome.warnings {
+//!             println!("warning: {}", warning);
+//!         }
 //!         Ok(())
 //!     });
 //! ```
+//!
+//! ## Progress
+//!
+//! Optimizing rewrites the whole file, which can take minutes for large files.
+//! `Subler::progress` receives the phase and percentage as SublerCli prints them,
+//! forward them to a channel to consume them elsewhere:
+//!
+//! ```rust,no_run
+//! use std::sync::{mpsc, Mutex};
+//! use sublercli::*;
+//! let (tx, rx) = mpsc::channel();
+//! let tx = Mutex::new(tx);
+//! std::thread::spawn(move || {
+//!     for progress in rx {
+//!         let progress: Progress = progress;
+//!         println!("{:?}: {:?}%", progress.phase, progress.percent);
+//!     }
+//! });
+//! let outcome = Subler::new("demo.mp4", Atoms::new().title("Foo Bar Title").build())
+//!     .progress(move |progress| {
+//!         let _ = tx.lock().unwrap().send(progress);
+//!     })
+//!     .tag();
+//! ```
+//!
+//! ## Timeouts and cancellation
+//!
+//! A stuck run can be bounded with a timeout, or stopped from another thread with a
+//! `CancelHandle`. Either way the process is killed, the partial output is removed and
+//! `SublerError::Timeout` or `SublerError::Cancelled` is returned:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use sublercli::*;
+//! let mut subler = Subler::new("demo.mp4", Atoms::new().title("Foo Bar Title").build());
+//! subler.timeout(Some(Duration::from_secs(600)));
+//! let handle = subler.cancel_handle();
+//! std::thread::spawn(move || {
+//!     std::thread::sleep(Duration::from_secs(60));
+//!     handle.cancel();
+//! });
+//! match subler.tag() {
+//!     Err(SublerError::Timeout(_)) | Err(SublerError::Cancelled) => println!("stopped"),
+//!     other => println!("{:?}", other),
+//! }
+//! ```
+//!
+//! ## Async
+//!
+//! With the `tokio` cargo feature, `Subler::tag_async` and `Subler::spawn_tag_async` run
+//! SublerCli with `tokio::process` instead of blocking the current thread. Other backends
+//! are run on tokio's blocking thread pool by `tag_async`:
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "tokio")]
+//! # async fn run() -> sublercli::Result<()> {
+//! use sublercli::*;
+//! let outcome = Subler::new("demo.mp4", Atoms::new().title("Foo Bar Title").build())
+//!     .tag_async()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Artwork normalization
+//!
+//! With the `image` cargo feature, `Subler::normalize_artwork` scales covers down to a
+//! longest side of 1400 pixels by default and re-encodes them as JPEG without EXIF data
+//! before they are handed to the backend:
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "image")]
+//! # fn run() -> sublercli::Result<()> {
+//! use sublercli::*;
+//! let outcome = Subler::new("demo.mp4", Atoms::new().artwork("poster.png").build())
+//!     .normalize_artwork(Some(ArtworkOptions {
+//!         quality: 85,
+//!         ..ArtworkOptions::default()
+//!     }))
+//!     .tag()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Backends
+//!
+//! The tagging itself is performed by a `TagBackend`. By default this is `SublerCli`,
+//! which runs the SublerCli process. Other implementations can be set with `Subler::backend`.
+//! `NativeBackend` writes the metadata with the pure Rust MP4 writer in `mp4`
+//! and works on every platform:
+//!
+//! ```rust,no_run
+//! use sublercli::*;
+//! let outcome = Subler::new("demo.mp4", Atoms::new().title("Foo Bar Title").build())
+//!     .backend(NativeBackend)
+//!     .tag();
+//! ```
 
 #![deny(warnings)]
-use std::io;
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Output};
+use std::process::{Child, Command};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod artwork;
+mod backend;
+mod cancel;
+pub mod chapters;
+mod date;
+mod error;
+mod genre;
+mod language;
+pub mod mp4;
+#[cfg(feature = "image")]
+mod normalize;
+mod progress;
+mod rating;
+mod shell;
+mod subtitles;
+#[cfg(test)]
+mod test_util;
+mod validate;
+mod value;
+
+pub use artwork::Artwork;
+pub use backend::{NativeBackend, SublerCli, TagBackend, TagRequest};
+pub use cancel::CancelHandle;
+pub use chapters::Chapter;
+pub use date::ReleaseDate;
+pub use error::{Result, SublerError};
+pub use genre::{Genre, GenreKind};
+pub use language::Language;
+#[cfg(feature = "image")]
+pub use normalize::ArtworkOptions;
+pub use progress::{Phase, Progress, ProgressCallback};
+pub use rating::{ContentRating, RatingSystem};
+pub use subtitles::Subtitle;
+pub use validate::AtomProblem;
+pub use value::{AtomKind, AtomValue};
 
 /// Represents the type of media for a input file
 #[derive(Debug, Clone)]
@@ -94,12 +304,80 @@ impl MediaKind {
         }
     }
 
+    /// The numeric code of the kind, as stored in the `stik` item of a file
+    pub fn stik(&self) -> u8 {
+        match self {
+            MediaKind::Music => 1,
+            MediaKind::Audiobook => 2,
+            MediaKind::MusicVideo => 6,
+            MediaKind::Movie => 9,
+            MediaKind::TVShow => 10,
+            MediaKind::Booklet => 11,
+            MediaKind::Rightone => 14,
+        }
+    }
+
+    /// The kind for a `stik` code
+    pub fn from_stik(code: u8) -> Option<MediaKind> {
+        match code {
+            0 | 9 => Some(MediaKind::Movie),
+            1 => Some(MediaKind::Music),
+            2 => Some(MediaKind::Audiobook),
+            6 => Some(MediaKind::MusicVideo),
+            10 => Some(MediaKind::TVShow),
+            11 => Some(MediaKind::Booklet),
+            14 => Some(MediaKind::Rightone),
+            _ => None,
+        }
+    }
+
+    /// The kind for its name as returned by `as_str`, ignoring case
+    pub fn from_name(name: &str) -> Option<MediaKind> {
+        let kinds = [
+            MediaKind::Movie,
+            MediaKind::Music,
+            MediaKind::Audiobook,
+            MediaKind::MusicVideo,
+            MediaKind::TVShow,
+            MediaKind::Booklet,
+            MediaKind::Rightone,
+        ];
+        if name.eq_ignore_ascii_case("Ringtone") {
+            return Some(MediaKind::Rightone);
+        }
+        kinds
+            .iter()
+            .find(|kind| kind.as_str().eq_ignore_ascii_case(name.trim()))
+            .cloned()
+    }
+
     /// Creates a new `Media Kind`
     pub fn as_atom(&self) -> Atom {
         Atom::new("Media Kind", self.as_str())
     }
 }
 
+/// The audio downmix applied by SublerCli's `-downmix`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Downmix {
+    Mono,
+    Stereo,
+    Dolby,
+    ProLogicII,
+}
+
+impl Downmix {
+    /// The value SublerCli expects for `-downmix`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Downmix::Mono => "mono",
+            Downmix::Stereo => "stereo",
+            Downmix::Dolby => "dolby",
+            Downmix::ProLogicII => "pl2",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Subler {
     /// The path to the source file
@@ -108,10 +386,37 @@ pub struct Subler {
     pub dest: Option<String>,
     /// The Subler optimization flag
     pub optimize: bool,
+    /// Whether all existing metadata of the source is removed before tagging
+    pub remove_existing: bool,
+    /// The audio downmix, if any
+    pub downmix: Option<Downmix>,
+    /// Whether tracks are enabled and grouped the way iTunes expects
+    pub itunes_friendly: bool,
+    /// Whether tracks are organized into alternate groups
+    pub organize_groups: bool,
+    /// The chapter markers that are imported into the file
+    pub chapters: Vec<Chapter>,
+    /// The subtitle files that are added as tracks
+    pub subtitles: Vec<Subtitle>,
     /// The atoms that should be written to the file
     pub atoms: Atoms,
-    /// The Mediakind of the file
+    /// The Mediakind of the file, unless the atoms already contain a Media Kind
     pub media_kind: Option<MediaKind>,
+    /// The longest a tagging run may take before it is stopped, no limit if `None`
+    pub timeout: Option<Duration>,
+    /// Whether the atoms are checked with `Atoms::validate` before tagging
+    pub validate: bool,
+    /// Whether `genreID` is filled in from the genre with `Atoms::fill_genre_id`
+    pub auto_genre_id: bool,
+    /// How covers are normalized before tagging, they're embedded as they are if `None`
+    #[cfg(feature = "image")]
+    pub artwork_options: Option<ArtworkOptions>,
+    /// Cancels running tagging runs of this instance
+    cancel: CancelHandle,
+    /// Receives the progress of tagging runs
+    progress: Option<ProgressCallback>,
+    /// The backend that performs the tagging
+    backend: Arc<dyn TagBackend>,
 }
 
 impl Subler {
@@ -124,8 +429,22 @@ impl Subler {
             source: source.to_owned(),
             dest: None,
             optimize: true,
+            remove_existing: false,
+            downmix: None,
+            itunes_friendly: false,
+            organize_groups: false,
+            chapters: Vec::new(),
+            subtitles: Vec::new(),
             atoms,
             media_kind: Some(MediaKind::Movie),
+            timeout: None,
+            validate: false,
+            auto_genre_id: false,
+            #[cfg(feature = "image")]
+            artwork_options: None,
+            cancel: CancelHandle::new(),
+            progress: None,
+            backend: Arc::new(SublerCli::new()),
         }
     }
 
@@ -137,48 +456,303 @@ impl Subler {
     }
 
     /// Executes the tagging command as a child process, returning a handle to it.
-    pub fn spawn_tag(&mut self) -> io::Result<Child> {
-        let mut cmd = self.build_tag_command()?;
-        cmd.spawn()
+    ///
+    /// Subtitles are added by separate processes after tagging, so they can't be
+    /// combined with `spawn_tag`. The timeout and the `CancelHandle` don't apply to the
+    /// spawned process, it's up to the caller to kill it.
+    ///
+    /// The chapter file and in-memory covers are written to temporary files, which are
+    /// left behind since the process still reads them. To remove them once the process
+    /// exited, spawn it from the request yourself:
+    ///
+    /// ```rust,no_run
+    /// use sublercli::*;
+    /// let subler = Subler::new("/movies/demo.mp4", Atoms::new().title("Foo").build());
+    /// let cli = subler.cli();
+    /// let request = subler.request().unwrap();
+    /// cli.prepare(&request).unwrap();
+    /// let status = cli.command(&request).unwrap().status().unwrap();
+    /// request.remove_temp_files();
+    /// ```
+    pub fn spawn_tag(&mut self) -> Result<Child> {
+        let cli = self.cli();
+        let request = self.request()?;
+        if !request.subtitles.is_empty() {
+            return Err(SublerError::Unsupported("Subtitle import with spawn_tag"));
+        }
+        cli.prepare(&request)?;
+        cli.command(&request)?
+            .spawn()
+            .map_err(|err| cli.spawn_error(err))
+    }
+
+    /// Executes the tagging command as a `tokio` child process, returning a handle to it.
+    ///
+    /// Like `spawn_tag`, subtitles can't be combined with this and the temporary files
+    /// are left behind
+    #[cfg(feature = "tokio")]
+    pub fn spawn_tag_async(&mut self) -> Result<tokio::process::Child> {
+        let cli = self.cli();
+        let request = self.request()?;
+        if !request.subtitles.is_empty() {
+            return Err(SublerError::Unsupported(
+                "Subtitle import with spawn_tag_async",
+            ));
+        }
+        cli.prepare(&request)?;
+        tokio::process::Command::from(cli.command(&request)?)
+            .spawn()
+            .map_err(|err| cli.spawn_error(err))
     }
 
     /// create the subler process command
-    pub fn build_tag_command(&mut self) -> io::Result<Command> {
+    ///
+    /// Subtitles are imported by further commands, a request with subtitles fails with
+    /// `SublerError::Unsupported`, see `build_commands`. Chapters and in-memory covers are passed to SublerCli in temporary files that only
+    /// exist while `tag` runs, they fail with `SublerError::Unsupported`. Use `request`
+    /// with `SublerCli::prepare` and `TagRequest::remove_temp_files` to run them yourself.
+    pub fn build_tag_command(&self) -> Result<Command> {
+        let request = self.request()?;
+        if !request.subtitles.is_empty() {
+            return Err(SublerError::Unsupported(
+                "Subtitle import with build_tag_command, see build_commands",
+            ));
+        }
+        if request.has_temp_files() {
+            return Err(SublerError::Unsupported(
+                "Chapters and in-memory covers with build_tag_command",
+            ));
+        }
+        self.cli().command(&request)
+    }
+
+    /// create all subler process commands in the order they are run by `tag`:
+    /// the tagging command followed by one command per subtitle track
+    ///
+    /// Like `build_tag_command`, chapters and in-memory covers are not supported.
+    pub fn build_commands(&self) -> Result<Vec<Command>> {
+        let request = self.request()?;
+        if request.has_temp_files() {
+            return Err(SublerError::Unsupported(
+                "Chapters and in-memory covers with build_commands",
+            ));
+        }
+        self.commands(&request)
+    }
+
+    /// All commands `tag` runs for the request, without writing the files they refer to
+    fn commands(&self, request: &TagRequest) -> Result<Vec<Command>> {
+        let cli = self.cli();
+        let mut commands = vec![cli.command(request)?];
+        commands.extend(cli.subtitle_commands(request));
+        Ok(commands)
+    }
+
+    /// The SublerCli invocation of the tagging command as a shell-quoted command line
+    ///
+    /// Nothing is written and no destination is created. The command line is always
+    /// rendered for SublerCli, the one of `cli`, regardless of the configured backend.
+    ///
+    /// ```rust,no_run
+    /// use sublercli::*;
+    /// let subler = Subler::new("demo.mp4", Atoms::new().title("It's me").build());
+    /// println!("{}", subler.command_line().unwrap());
+    /// ```
+    pub fn command_line(&self) -> Result<String> {
+        let request = self.request()?;
+        Ok(shell::command_line(&self.cli().command(&request)?))
+    }
+
+    /// The command lines of all processes `tag` runs with SublerCli, see `command_line`
+    ///
+    /// Chapters and in-memory covers are rendered with the paths of the temporary files
+    /// `tag` would write them to.
+    pub fn dry_run(&self) -> Result<Vec<String>> {
+        let request = self.request()?;
+        Ok(self
+            .commands(&request)?
+            .iter()
+            .map(shell::command_line)
+            .collect())
+    }
+
+    /// resolves source, destination and atoms into the request handed to the backend
+    pub fn request(&self) -> Result<TagRequest> {
         let path = Path::new(self.source.as_str());
         if !path.exists() {
-            return Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                "Source file does not exist.".to_owned(),
-            ));
+            return Err(SublerError::SourceNotFound(path.to_path_buf()));
         }
+        let mut atoms = self.atoms.clone();
         if let Some(ref media_kind) = self.media_kind {
-            self.atoms.add_atom(media_kind.as_atom());
+            // a Media Kind atom set explicitly takes precedence
+            atoms.insert(media_kind.as_atom(), OnDuplicate::KeepFirst);
+        }
+        if self.auto_genre_id {
+            let kind = atoms
+                .get("Media Kind")
+                .and_then(|atom| MediaKind::from_name(&atom.value.to_string()))
+                .and_then(GenreKind::for_media_kind);
+            if let Some(kind) = kind {
+                atoms.fill_genre_id(kind);
+            }
+        }
+        // relative artwork paths are relative to the source, not the working directory
+        atoms.resolve_artwork(path.parent().unwrap_or_else(|| Path::new("")));
+        // a broken date is reported among all other problems, not on its own
+        if self.validate {
+            atoms.validate()?;
+        }
+        atoms.normalize_dates()?;
+        // a missing or broken cover would only fail SublerCli after rewriting the file
+        for artwork in atoms.artworks() {
+            artwork.format()?;
+        }
+        #[cfg(feature = "image")]
+        {
+            if let Some(ref options) = self.artwork_options {
+                atoms.normalize_artwork(options)?;
+            }
         }
 
-        let dest = self
-            .determine_dest()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Dest Not Found!"))?;
-        let atoms = self.atoms.args();
-        let mut args = vec!["-source", self.source.as_str()];
-        args.push("-dest");
-        args.push(dest.as_str());
-        let meta_tags: Vec<&str> = atoms.iter().map(AsRef::as_ref).collect();
-        args.extend(meta_tags);
-
-        if self.optimize {
-            args.push("-optimize");
+        if let Some(subtitle) = self.subtitles.iter().find(|s| !s.path.exists()) {
+            return Err(SublerError::SubtitleNotFound(subtitle.path.clone()));
         }
 
-        let mut cmd = Command::new(Subler::cli_executeable().as_str());
-        cmd.args(&args);
-        Ok(cmd)
+        let dest = self.determine_dest().ok_or_else(|| {
+            SublerError::InvalidDestination(PathBuf::from(
+                self.dest.as_ref().unwrap_or(&self.source),
+            ))
+        })?;
+        Ok(TagRequest {
+            source: path.to_path_buf(),
+            dest: PathBuf::from(dest),
+            atoms,
+            optimize: self.optimize,
+            remove_existing: self.remove_existing,
+            downmix: self.downmix,
+            itunes_friendly: self.itunes_friendly,
+            organize_groups: self.organize_groups,
+            chapters: self.chapters.clone(),
+            subtitles: self.subtitles.clone(),
+            timeout: self.timeout,
+            cancel: self.cancel.clone(),
+            progress: self.progress.clone(),
+            run: TagRequest::next_run(),
+        })
     }
 
     /// Apply the specified metadata to the source file and output it to
-    /// the specified destination file
-    pub fn tag(&mut self) -> io::Result<Output> {
-        let mut cmd = self.build_tag_command()?;
-        cmd.output()
+    /// the specified destination file, using the configured backend
+    ///
+    /// A non-zero exit status of SublerCli is reported as `SublerError::CliFailed`.
+    /// If the run exceeds the `timeout` or is cancelled with the `cancel_handle`, the process
+    /// is killed, the partially written destination is removed and
+    /// `SublerError::Timeout` or `SublerError::Cancelled` is returned.
+    pub fn tag(&mut self) -> Result<TagOutcome> {
+        let request = self.request()?;
+        self.backend.tag(&request)
+    }
+
+    /// The names of all languages the SublerCli of `cli` knows, from its `-listlanguages`
+    pub fn list_languages(&self) -> Result<Vec<String>> {
+        self.cli().list_languages()
+    }
+
+    /// Reads the existing metadata of the file at `path` with the `-listmetadata` of the
+    /// SublerCli of `cli`
+    pub fn list_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Atoms> {
+        self.cli().list_metadata(path)
+    }
+
+    /// The SublerCli the commands are built and spawned with: the configured backend if
+    /// it is a `SublerCli`, the one at `cli_executeable()` otherwise
+    pub fn cli(&self) -> SublerCli {
+        self.backend.sublercli().cloned().unwrap_or_default()
+    }
+
+    /// Apply the specified metadata like `tag`, without blocking the current thread
+    ///
+    /// SublerCli is run with `tokio::process`, other backends on tokio's blocking thread
+    /// pool. The future is `Send`, so it can be spawned:
+    ///
+    /// ```rust,no_run
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// use sublercli::*;
+    /// let mut subler = Subler::new("demo.mp4", Atoms::new().title("Foo").build());
+    /// let outcome = tokio::spawn(async move { subler.tag_async().await })
+    ///     .await
+    ///     .unwrap()
+    ///     .unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn tag_async(&mut self) -> Result<TagOutcome> {
+        let request = self.request()?;
+        if let Some(cli) = self.backend.sublercli() {
+            return cli.tag_async(&request).await;
+        }
+        let backend = Arc::clone(&self.backend);
+        tokio::task::spawn_blocking(move || backend.tag(&request))
+            .await
+            .map_err(|err| SublerError::Io(std::io::Error::other(err)))?
+    }
+
+    /// sets the backend that performs the tagging, `SublerCli` by default
+    pub fn backend<B: TagBackend + 'static>(&mut self, backend: B) -> &mut Self {
+        self.backend = Arc::new(backend);
+        self
+    }
+
+    /// sets whether the atoms are checked with `Atoms::validate` before anything is run,
+    /// failing with `SublerError::InvalidAtoms`
+    ///
+    /// ```rust,no_run
+    /// use sublercli::*;
+    /// let atoms = Atoms::new().add("Release Date", "03/01/2018").build();
+    /// match Subler::new("demo.mp4", atoms).validate(true).tag() {
+    ///     Err(SublerError::InvalidAtoms(problems)) => println!("{:?}", problems),
+    ///     other => println!("{:?}", other),
+    /// }
+    /// ```
+    pub fn validate(&mut self, val: bool) -> &mut Self {
+        self.validate = val;
+        self
+    }
+
+    /// sets whether `genreID` is filled in from the genre of the atoms, looked up in the
+    /// store catalog of the media kind
+    pub fn auto_genre_id(&mut self, val: bool) -> &mut Self {
+        self.auto_genre_id = val;
+        self
+    }
+
+    /// sets how covers are scaled down and recompressed before tagging, see
+    /// `Artwork::normalize`
+    #[cfg(feature = "image")]
+    pub fn normalize_artwork(&mut self, options: Option<ArtworkOptions>) -> &mut Self {
+        self.artwork_options = options;
+        self
+    }
+
+    /// sets the longest a tagging run may take, covering all processes of the run
+    pub fn timeout(&mut self, timeout: Option<Duration>) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// A handle to cancel the tagging runs of this instance from another thread
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// sets a function that receives the progress while `tag` runs
+    ///
+    /// SublerCli's output is parsed as it is printed, see `Progress::parse`. The function
+    /// is called on the thread running `tag`, `NativeBackend` reports no progress.
+    pub fn progress<F: Fn(Progress) + Send + Sync + 'static>(&mut self, callback: F) -> &mut Self {
+        self.progress = Some(ProgressCallback::new(callback));
+        self
     }
 
     /// sets the optimization flag
@@ -187,6 +761,43 @@ impl Subler {
         self
     }
 
+    /// sets the audio downmix, SublerCli's `-downmix`
+    pub fn downmix(&mut self, downmix: Option<Downmix>) -> &mut Self {
+        self.downmix = downmix;
+        self
+    }
+
+    /// sets the iTunes friendly flag, SublerCli's `-itunesfriendly`
+    pub fn itunes_friendly(&mut self, val: bool) -> &mut Self {
+        self.itunes_friendly = val;
+        self
+    }
+
+    /// sets the alternate group organization flag, SublerCli's `-organizegroups`
+    pub fn organize_groups(&mut self, val: bool) -> &mut Self {
+        self.organize_groups = val;
+        self
+    }
+
+    /// sets the chapter markers that are imported into the file with SublerCli's `-chapters`
+    pub fn chapters(&mut self, chapters: Vec<Chapter>) -> &mut Self {
+        self.chapters = chapters;
+        self
+    }
+
+    /// adds a subtitle file as a new track, with its own language, delay and height
+    pub fn subtitle(&mut self, subtitle: Subtitle) -> &mut Self {
+        self.subtitles.push(subtitle);
+        self
+    }
+
+    /// removes all existing metadata of the source before the atoms are written,
+    /// SublerCli's `-remove`
+    pub fn remove_existing(&mut self, val: bool) -> &mut Self {
+        self.remove_existing = val;
+        self
+    }
+
     pub fn media_kind(&mut self, kind: Option<MediaKind>) -> &mut Self {
         self.media_kind = kind;
         self
@@ -238,30 +849,280 @@ impl Subler {
     }
 }
 
+/// The result of a successful tagging run
+#[derive(Debug, Clone)]
+pub struct TagOutcome {
+    /// The path of the file that was actually written
+    pub dest: PathBuf,
+    /// Warnings printed by SublerCli while tagging
+    pub warnings: Vec<String>,
+    /// How long the tagging run took
+    pub elapsed: Duration,
+    /// The complete stdout of the process
+    pub stdout: String,
+    /// The complete stderr of the process
+    pub stderr: String,
+}
+
+impl TagOutcome {
+    /// Creates an outcome for the given destination without any warnings
+    pub fn new(dest: PathBuf) -> Self {
+        TagOutcome {
+            dest,
+            warnings: Vec::new(),
+            elapsed: Duration::default(),
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+
+    /// Creates the outcome of a finished SublerCli run that started at `start`
+    pub(crate) fn from_output(
+        request: &TagRequest,
+        stdout: String,
+        stderr: String,
+        start: Instant,
+    ) -> Self {
+        TagOutcome {
+            warnings: TagOutcome::parse_warnings(&stdout, &stderr),
+            dest: request.dest.clone(),
+            elapsed: start.elapsed(),
+            stdout,
+            stderr,
+        }
+    }
+
+    /// Whether SublerCli printed any warnings
+    pub fn has_warnings(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+
+    /// collects the warnings of a successful run: every line on stderr and
+    /// every line on stdout that announces itself as a warning
+    pub(crate) fn parse_warnings(stdout: &str, stderr: &str) -> Vec<String> {
+        let mut warnings: Vec<String> = Vec::new();
+        let stdout_warnings = stdout
+            .lines()
+            .filter(|line| line.trim_start().to_lowercase().starts_with("warning"));
+        for line in stdout_warnings.chain(stderr.lines()) {
+            let line = line.trim();
+            if !line.is_empty() && !warnings.iter().any(|w| w == line) {
+                warnings.push(line.to_owned());
+            }
+        }
+        warnings
+    }
+}
+
 /// Represents a Metadata Media Atom
 #[derive(Debug, Clone)]
 pub struct Atom {
     /// The Name of the Metadata Atom
     pub tag: String,
     /// The Value this atom contains
-    pub value: String,
+    pub value: AtomValue,
 }
 
 impl Atom {
+    /// Creates an atom holding text
     pub fn new(tag: &str, val: &str) -> Atom {
+        Atom::with_value(tag, AtomValue::from(val))
+    }
+
+    /// Creates an atom holding a typed value
+    pub fn with_value(tag: &str, value: AtomValue) -> Atom {
         Atom {
             tag: tag.to_owned(),
-            value: val.to_owned(),
+            value,
+        }
+    }
+
+    /// Creates an atom with the text parsed as the kind of the tag, see `AtomValue::parse`
+    ///
+    /// Text that isn't a value of the kind, and the text of unknown tags, is kept as text.
+    pub fn parse(tag: &str, text: &str) -> Atom {
+        let value = Atoms::tag_kind(tag)
+            .and_then(|kind| AtomValue::parse(kind, text))
+            .unwrap_or_else(|| AtomValue::from(text));
+        Atom::with_value(tag, value)
+    }
+
+    /// Creates an atom that deletes the tag from the file
+    pub fn deletion(tag: &str) -> Atom {
+        Atom::new(tag, "")
+    }
+
+    /// Whether this atom deletes its tag from the file instead of setting a value
+    pub fn is_deletion(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// The `{tag:value}` form of the atom in SublerCli's `-metadata` argument
+    ///
+    /// SublerCli splits the tag from the value at the first colon and has no way to escape
+    /// braces, so colons are fine in values, but atoms that can't be passed unambiguously
+    /// are rejected with `SublerError::AmbiguousAtom`:
+    ///
+    /// ```rust
+    /// use sublercli::{Atom, SublerError};
+    /// let atom = Atom::new("Name", "Star Wars: Episode IV");
+    /// assert_eq!(atom.arg().unwrap(), "{Name:Star Wars: Episode IV}");
+    /// assert_eq!(Atom::deletion("Name").arg().unwrap(), "{Name:}");
+    ///
+    /// for (tag, value) in &[
+    ///     ("Name", "Star Wars: Episode IV {Remastered}"),
+    ///     ("Name", "}{Genre:Drama"),
+    ///     ("Name", "{"),
+    ///     ("Na:me", "Foo"),
+    ///     ("{Name}", "Foo"),
+    ///     ("", "Foo"),
+    /// ] {
+    ///     match Atom::new(tag, value).arg() {
+    ///         Err(SublerError::AmbiguousAtom { .. }) => {}
+    ///         other => panic!("{:?} for {}: {}", other, tag, value),
+    ///     }
+    /// }
+    /// ```
+    pub fn arg(&self) -> Result<String> {
+        let value = self.value.to_string();
+        let ambiguous =
+            self.tag.is_empty() || self.tag.contains(['{', '}', ':']) || value.contains(['{', '}']);
+        if ambiguous {
+            return Err(SublerError::AmbiguousAtom {
+                tag: self.tag.clone(),
+                value,
+            });
+        }
+        Ok(format!("{{{}:{}}}", self.tag, value))
+    }
+}
+
+impl Atoms {
+    /// Parses the output of SublerCli's `-listmetadata` mode
+    ///
+    /// Every line starting with one of the `metadata_tags()` followed by a colon starts a new
+    /// atom, all other lines continue the value of the previous atom. The values are parsed
+    /// as the kind of their tag, see `Atom::parse`.
+    ///
+    /// ```rust
+    /// use sublercli::{AtomValue, Atoms};
+    /// let atoms = Atoms::from_metadata_listing(
+    ///     "Name: Star Wars: Episode IV\nTrack #: 3/12\nDescription: first line\nsecond line\n",
+    /// );
+    /// let expected = Atoms::new()
+    ///     .title("Star Wars: Episode IV")
+    ///     .track_number(3, Some(12))
+    ///     .description("first line\nsecond line")
+    ///     .build();
+    /// let values = |atoms: &Atoms| -> Vec<(String, AtomValue)> {
+    ///     atoms
+    ///         .atoms()
+    ///         .iter()
+    ///         .map(|a| (a.tag.clone(), a.value.clone()))
+    ///         .collect()
+    /// };
+    /// assert_eq!(values(&atoms), values(&expected));
+    /// ```
+    pub fn from_metadata_listing(listing: &str) -> Atoms {
+        let tags = Atoms::metadata_tags();
+        let mut entries: Vec<(&str, String)> = Vec::new();
+        for line in listing.lines() {
+            let tag = tags
+                .iter()
+                .filter(|tag| line.starts_with(*tag) && line[tag.len()..].starts_with(':'))
+                .max_by_key(|tag| tag.len());
+            match (tag, entries.last_mut()) {
+                (Some(tag), _) => entries.push((tag, line[tag.len() + 1..].trim().to_owned())),
+                (None, Some((_, value))) => {
+                    value.push('\n');
+                    value.push_str(line.trim_end());
+                }
+                (None, None) => {}
+            }
+        }
+        let mut atoms = Atoms { inner: Vec::new() };
+        for (tag, value) in entries {
+            atoms.insert(Atom::parse(tag, value.trim_end()), OnDuplicate::Append);
         }
+        atoms
     }
-    pub fn arg(&self) -> String {
-        format!("{{{}:{}}}", self.tag, self.value)
+}
+
+/// What happens when an atom is added for a tag that is already present
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnDuplicate {
+    /// The new atom replaces all atoms of the tag, at the position of the first one
+    Replace,
+    /// The new atom is added after the last atom of the tag, like additional covers
+    Append,
+    /// The new atom is dropped
+    KeepFirst,
+}
+
+/// The spelling of the tag in `Atoms::metadata_tags()` if it's known, ignoring case
+fn canonical_tag(tag: &str) -> String {
+    Atoms::metadata_tags()
+        .into_iter()
+        .find(|known| known.eq_ignore_ascii_case(tag))
+        .unwrap_or(tag)
+        .to_owned()
+}
+
+/// Adds the atom to a list kept grouped by canonical tag name
+fn insert_atom(atoms: &mut Vec<Atom>, mut atom: Atom, on_duplicate: OnDuplicate) {
+    atom.tag = canonical_tag(&atom.tag);
+    let first = atoms.iter().position(|a| a.tag == atom.tag);
+    match (first, on_duplicate) {
+        (None, _) => atoms.push(atom),
+        (Some(_), OnDuplicate::KeepFirst) => {}
+        (Some(first), OnDuplicate::Replace) => {
+            atoms.retain(|a| a.tag != atom.tag);
+            atoms.insert(first, atom);
+        }
+        (Some(_), OnDuplicate::Append) => {
+            let last = atoms.iter().rposition(|a| a.tag == atom.tag).unwrap_or(0);
+            atoms.insert(last + 1, atom);
+        }
     }
 }
 
+/// A setter of `Atoms` and `Builder` that takes the Rust type of the tag's `AtomKind`
+macro_rules! atom_setter {
+    ($ident:ident, $tag:expr, Text) => {
+        pub fn $ident(&mut self, val: &str) -> &mut Self {
+            self.add_atom(Atom::new($tag, val))
+        }
+    };
+    ($ident:ident, $tag:expr, Integer) => {
+        pub fn $ident(&mut self, val: i64) -> &mut Self {
+            self.add_atom(Atom::with_value($tag, AtomValue::Integer(val)))
+        }
+    };
+    ($ident:ident, $tag:expr, Boolean) => {
+        pub fn $ident(&mut self, val: bool) -> &mut Self {
+            self.add_atom(Atom::with_value($tag, AtomValue::Boolean(val)))
+        }
+    };
+    ($ident:ident, $tag:expr, Date) => {
+        pub fn $ident<D: Into<ReleaseDate>>(&mut self, date: D) -> &mut Self {
+            self.add_atom(Atom::with_value($tag, AtomValue::Date(date.into())))
+        }
+    };
+    ($ident:ident, $tag:expr, Pair) => {
+        pub fn $ident(&mut self, number: u16, total: Option<u16>) -> &mut Self {
+            self.add_atom(Atom::with_value($tag, AtomValue::Pair { number, total }))
+        }
+    };
+    ($ident:ident, $tag:expr, Image) => {
+        pub fn $ident<A: Into<Artwork>>(&mut self, artwork: A) -> &mut Self {
+            self.add_atom(Atom::with_value($tag, AtomValue::Image(artwork.into())))
+        }
+    };
+}
+
 macro_rules! atom_tag {
 
-    ( $($ident:tt : $tag:expr),*) => {
+    ( $($ident:tt : $tag:expr => $kind:ident),*) => {
         #[derive(Debug, Clone)]
         pub struct Atoms {
             /// The stored atoms
@@ -270,78 +1131,150 @@ macro_rules! atom_tag {
 
         impl Atoms {
 
+            #[allow(clippy::new_ret_no_self)]
             pub fn new() -> Builder {
                 Builder::default()
             }
 
             /// all valid Metadata Atom tags
             pub fn metadata_tags<'a>() -> Vec<&'a str> {
-                let mut params = Vec::new();
-                $(
-                    params.push($tag);
-                )*
-                params
+                vec![$($tag),*]
+            }
 
+            /// The kind of value the tag holds, `None` for unknown tags
+            pub fn tag_kind(tag: &str) -> Option<AtomKind> {
+                [$(($tag, AtomKind::$kind)),*]
+                    .iter()
+                    .find(|(known, _)| *known == tag)
+                    .map(|(_, kind)| *kind)
             }
 
             $(
-                pub fn $ident(&mut self, val: &str) -> &mut Self{
-                    self.inner.push(Atom::new($tag, val));
-                    self
-                }
+                atom_setter!($ident, $tag, $kind);
             )*
 
             /// args for setting the metaflag flag of subler
-            pub fn args(&self) -> Vec<String> {
+            ///
+            /// fails if any atom can't be encoded, see `Atom::arg`
+            pub fn args(&self) -> Result<Vec<String>> {
                 let mut args = Vec::new();
                 if !self.inner.is_empty(){
                     args.push("-metadata".to_owned());
-                    args.push(self.inner.iter().map(Atom::arg).collect::<Vec<_>>().join(""));
+                    args.push(self.inner.iter().map(Atom::arg).collect::<Result<Vec<_>>>()?.join(""));
                 }
-                args
+                Ok(args)
             }
 
+            /// adds the atom, replacing all existing atoms of its tag
             pub fn add_atom(&mut self, atom: Atom) -> &mut Self {
-                self.inner.push(atom);
+                self.insert(atom, OnDuplicate::Replace)
+            }
+
+            /// adds the atom, `on_duplicate` decides what happens if its tag is present
+            pub fn insert(&mut self, atom: Atom, on_duplicate: OnDuplicate) -> &mut Self {
+                insert_atom(&mut self.inner, atom, on_duplicate);
                 self
             }
 
             pub fn add(&mut self, tag: &str, val: &str) -> &mut Self {
-                self.inner.push(Atom::new(tag, val));
+                self.add_atom(Atom::new(tag, val))
+            }
+
+            /// marks the tag for deletion from the file
+            pub fn delete(&mut self, tag: &str) -> &mut Self {
+                self.add_atom(Atom::deletion(tag))
+            }
+
+            /// sets the `Rating` to the iTunes rating string of the content rating
+            pub fn content_rating(&mut self, rating: ContentRating) -> &mut Self {
+                self.add_atom(Atom::new("Rating", &rating.to_string()))
+            }
+
+            /// adds a further cover after the existing ones, `artwork` replaces all covers
+            pub fn add_artwork<A: Into<Artwork>>(&mut self, artwork: A) -> &mut Self {
+                let atom = Atom::with_value("Artwork", AtomValue::Image(artwork.into()));
+                self.insert(atom, OnDuplicate::Append)
+            }
+
+            /// removes all atoms of the tag
+            pub fn remove(&mut self, tag: &str) -> &mut Self {
+                let tag = canonical_tag(tag);
+                self.inner.retain(|a| a.tag != tag);
                 self
             }
 
+            /// the first atom of the tag
+            pub fn get(&self, tag: &str) -> Option<&Atom> {
+                let tag = canonical_tag(tag);
+                self.inner.iter().find(|a| a.tag == tag)
+            }
+
+            /// all atoms of the tag, in the order they were added
+            pub fn get_all(&self, tag: &str) -> Vec<&Atom> {
+                let tag = canonical_tag(tag);
+                self.inner.iter().filter(|a| a.tag == tag).collect()
+            }
+
+            pub fn contains(&self, tag: &str) -> bool {
+                self.get(tag).is_some()
+            }
+
             pub fn atoms(&self) -> &Vec<Atom> {
                 &self.inner
             }
 
-            pub fn atoms_mut(&mut self) -> &mut Vec<Atom> {
+            /// direct access to the atoms for in-place rewrites, like `normalize_dates`
+            pub(crate) fn atoms_mut(&mut self) -> &mut Vec<Atom> {
                 &mut self.inner
             }
         }
 
+        /// Builds `Atoms`, every atom is added through `insert` so tags stay unique
         #[derive(Debug)]
         pub struct Builder {
-            pub atoms: Vec<Atom>,
+            atoms: Vec<Atom>,
         }
 
         impl Builder {
 
-             $(
-                pub fn $ident(&mut self, val: &str) -> &mut Self{
-                    self.atoms.push(Atom::new($tag, val));
-                    self
-                }
+            $(
+                atom_setter!($ident, $tag, $kind);
             )*
 
+            /// adds the atom, replacing all existing atoms of its tag
             pub fn add_atom(&mut self, atom: Atom) -> &mut Self {
-                self.atoms.push(atom);
+                self.insert(atom, OnDuplicate::Replace)
+            }
+
+            /// adds the atom, `on_duplicate` decides what happens if its tag is present
+            pub fn insert(&mut self, atom: Atom, on_duplicate: OnDuplicate) -> &mut Self {
+                insert_atom(&mut self.atoms, atom, on_duplicate);
                 self
             }
 
             pub fn add(&mut self, tag: &str, val: &str) -> &mut Self {
-                self.atoms.push(Atom::new(tag, val));
-                self
+                self.add_atom(Atom::new(tag, val))
+            }
+
+            /// marks the tag for deletion from the file
+            pub fn delete(&mut self, tag: &str) -> &mut Self {
+                self.add_atom(Atom::deletion(tag))
+            }
+
+            /// sets the `Rating` to the iTunes rating string of the content rating
+            pub fn content_rating(&mut self, rating: ContentRating) -> &mut Self {
+                self.add_atom(Atom::new("Rating", &rating.to_string()))
+            }
+
+            /// adds a further cover after the existing ones, `artwork` replaces all covers
+            pub fn add_artwork<A: Into<Artwork>>(&mut self, artwork: A) -> &mut Self {
+                let atom = Atom::with_value("Artwork", AtomValue::Image(artwork.into()));
+                self.insert(atom, OnDuplicate::Append)
+            }
+
+            /// the atoms added so far
+            pub fn atoms(&self) -> &Vec<Atom> {
+                &self.atoms
             }
 
             pub fn build(&self) -> Atoms {
@@ -358,78 +1291,273 @@ macro_rules! atom_tag {
 }
 
 atom_tag!(
-    artist: "Artist",
-    album_artist: "Album Artist",
-    album: "Album",
-    grouping: "Grouping",
-    composer: "Composer",
-    comments: "Comments",
-    genre: "Genre",
-    release_date: "Release Date",
-    track_number: "Track #",
-    disk_number: "Disk #",
-    tempo: "Tempo",
-    tv_show: "TV Show",
-    tv_episode_number: "TV Episode #",
-    tv_network: "TV Network",
-    tv_episode_id: "TV Episode ID",
-    tv_season: "TV Season",
-    description: "Description",
-    long_description: "Long Description",
-    series_description: "Series Description",
-    hd_video: "HD Video",
-    rating_annotation: "Rating Annotation",
-    studio: "Studio",
-    cast: "Cast",
-    director: "Director",
-    gapless: "Gapless",
-    codirector: "Codirector",
-    producers: "Producers",
-    screenwriters: "Screenwriters",
-    lyrics: "Lyrics",
-    copyright: "Copyright",
-    encoding_tool: "Encoding Tool",
-    encoded_by: "Encoded By",
-    keywords: "Keywords",
-    category: "Category",
-    contentid: "contentID",
-    artistid: "artistID",
-    playlistid: "playlistID",
-    genreid: "genreID",
-    composerid: "composerID",
-    xid: "XID",
-    itunes_account: "iTunes Account",
-    itunes_account_type: "iTunes Account Type",
-    itunes_country: "iTunes Country",
-    track_sub_title: "Track Sub-Title",
-    song_description: "Song Description",
-    art_director: "Art Director",
-    arranger: "Arranger",
-    lyricist: "Lyricist",
-    acknowledgement: "Acknowledgement",
-    conductor: "Conductor",
-    linear_notes: "Linear Notes",
-    record_company: "Record Company",
-    original_artist: "Original Artist",
-    phonogram_rights: "Phonogram Rights",
-    producer: "Producer",
-    performer: "Performer",
-    publisher: "Publisher",
-    sound_engineer: "Sound Engineer",
-    soloist: "Soloist",
-    credits: "Credits",
-    thanks: "Thanks",
-    online_extras: "Online Extras",
-    executive_producer: "Executive Producer",
-    sort_name: "Sort Name",
-    sort_artist: "Sort Artist",
-    sort_album_artist: "Sort Album Artist",
-    sort_album: "Sort Album",
-    sort_composer: "Sort Composer",
-    sort_tv_show: "Sort TV Show",
-    artwork: "Artwork",
-    name: "Name",
-    title: "Name",
-    rating: "Rating",
-    media_kind: "Media Kind"
-   );
+    artist: "Artist" => Text,
+    album_artist: "Album Artist" => Text,
+    album: "Album" => Text,
+    grouping: "Grouping" => Text,
+    composer: "Composer" => Text,
+    comments: "Comments" => Text,
+    genre: "Genre" => Text,
+    release_date: "Release Date" => Date,
+    track_number: "Track #" => Pair,
+    disk_number: "Disk #" => Pair,
+    tempo: "Tempo" => Integer,
+    tv_show: "TV Show" => Text,
+    tv_episode_number: "TV Episode #" => Integer,
+    tv_network: "TV Network" => Text,
+    tv_episode_id: "TV Episode ID" => Text,
+    tv_season: "TV Season" => Integer,
+    description: "Description" => Text,
+    long_description: "Long Description" => Text,
+    series_description: "Series Description" => Text,
+    hd_video: "HD Video" => Boolean,
+    rating_annotation: "Rating Annotation" => Text,
+    studio: "Studio" => Text,
+    cast: "Cast" => Text,
+    director: "Director" => Text,
+    gapless: "Gapless" => Boolean,
+    codirector: "Codirector" => Text,
+    producers: "Producers" => Text,
+    screenwriters: "Screenwriters" => Text,
+    lyrics: "Lyrics" => Text,
+    copyright: "Copyright" => Text,
+    encoding_tool: "Encoding Tool" => Text,
+    encoded_by: "Encoded By" => Text,
+    keywords: "Keywords" => Text,
+    category: "Category" => Text,
+    contentid: "contentID" => Integer,
+    artistid: "artistID" => Integer,
+    playlistid: "playlistID" => Integer,
+    genreid: "genreID" => Integer,
+    composerid: "composerID" => Integer,
+    xid: "XID" => Text,
+    itunes_account: "iTunes Account" => Text,
+    itunes_account_type: "iTunes Account Type" => Integer,
+    itunes_country: "iTunes Country" => Integer,
+    track_sub_title: "Track Sub-Title" => Text,
+    song_description: "Song Description" => Text,
+    art_director: "Art Director" => Text,
+    arranger: "Arranger" => Text,
+    lyricist: "Lyricist" => Text,
+    acknowledgement: "Acknowledgement" => Text,
+    conductor: "Conductor" => Text,
+    linear_notes: "Linear Notes" => Text,
+    record_company: "Record Company" => Text,
+    original_artist: "Original Artist" => Text,
+    phonogram_rights: "Phonogram Rights" => Text,
+    producer: "Producer" => Text,
+    performer: "Performer" => Text,
+    publisher: "Publisher" => Text,
+    sound_engineer: "Sound Engineer" => Text,
+    soloist: "Soloist" => Text,
+    credits: "Credits" => Text,
+    thanks: "Thanks" => Text,
+    online_extras: "Online Extras" => Text,
+    executive_producer: "Executive Producer" => Text,
+    sort_name: "Sort Name" => Text,
+    sort_artist: "Sort Artist" => Text,
+    sort_album_artist: "Sort Album Artist" => Text,
+    sort_album: "Sort Album" => Text,
+    sort_composer: "Sort Composer" => Text,
+    sort_tv_show: "Sort TV Show" => Text,
+    artwork: "Artwork" => Image,
+    name: "Name" => Text,
+    title: "Name" => Text,
+    rating: "Rating" => Text,
+    media_kind: "Media Kind" => Text
+);
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_util;
+
+    #[test]
+    fn build_commands_reject_temporary_files() {
+        let source = test_util::source_file("build-temp-files");
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        subler.chapters(vec![Chapter::new(Duration::from_secs(0), "Intro")]);
+        assert!(subler.dry_run().unwrap()[0].contains(" -chapters "));
+        for result in &[
+            subler.build_tag_command().map(|_| ()),
+            subler.build_commands().map(|_| ()),
+        ] {
+            match result {
+                Err(SublerError::Unsupported(_)) => {}
+                other => panic!("{:?}", other),
+            }
+        }
+
+        let cover = Artwork::from_bytes(b"\x89PNG\r\n\x1a\n....".to_vec());
+        let mut subler = Subler::new(
+            source.to_str().unwrap(),
+            Atoms::new().artwork(cover).build(),
+        );
+        match subler.validate(false).build_tag_command() {
+            Err(SublerError::Unsupported(_)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn build_tag_command_rejects_subtitles() {
+        let source = test_util::source_file("build-subtitles");
+        let subtitle = source.with_file_name("demo.en.srt");
+        fs::write(&subtitle, b"").unwrap();
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        subler.subtitle(Subtitle::new(&subtitle)).optimize(true);
+        match subler.build_tag_command() {
+            Err(SublerError::Unsupported(_)) => {}
+            other => panic!("{:?}", other),
+        }
+        let commands = subler.build_commands().unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(subler.dry_run().unwrap()[1].ends_with(" -optimize"));
+    }
+
+    #[test]
+    fn missing_covers_and_subtitles_are_reported_as_such() {
+        let source = test_util::source_file("missing-files");
+        let atoms = Atoms::new()
+            .artwork(Artwork::from_path("missing-cover.jpg"))
+            .build();
+        match Subler::new(source.to_str().unwrap(), atoms)
+            .validate(false)
+            .request()
+        {
+            Err(SublerError::ArtworkNotFound(path)) => {
+                assert_eq!(path, source.with_file_name("missing-cover.jpg"))
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let subtitle = source.with_file_name("missing.en.srt");
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        match subler.subtitle(Subtitle::new(&subtitle)).request() {
+            Err(SublerError::SubtitleNotFound(path)) => assert_eq!(path, subtitle),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn builder_keeps_tags_unique() {
+        let mut builder = Atoms::new();
+        builder
+            .title("Foo")
+            .add("Name", "Bar")
+            .add_artwork(Artwork::from_path("a.jpg"));
+        builder.add_artwork(Artwork::from_path("b.jpg"));
+        let tags: Vec<_> = builder.atoms().iter().map(|a| a.tag.as_str()).collect();
+        assert_eq!(tags, vec!["Name", "Artwork", "Artwork"]);
+        assert_eq!(
+            builder.build().get("Name").unwrap().value.to_string(),
+            "Bar"
+        );
+    }
+
+    #[test]
+    fn parses_warnings_from_stdout_and_stderr() {
+        let stdout = "Muxing 50%\n  WARNING: track 2 skipped\nwarnings ahead\nDone\n";
+        let stderr = "\nlanguage undefined\n  WARNING: track 2 skipped  \n";
+        assert_eq!(
+            TagOutcome::parse_warnings(stdout, stderr),
+            vec![
+                "WARNING: track 2 skipped",
+                "warnings ahead",
+                "language undefined"
+            ]
+        );
+        assert!(TagOutcome::parse_warnings("Done\n", "").is_empty());
+    }
+
+    #[test]
+    fn dry_run_removes_existing_metadata() {
+        let source = test_util::source_file("dry-run-remove");
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        assert!(!subler.dry_run().unwrap()[0].contains(" -remove"));
+        let line = subler.remove_existing(true).command_line().unwrap();
+        assert!(line.contains(" -remove"), "{}", line);
+    }
+
+    #[test]
+    fn command_line_quotes_atoms_without_side_effects() {
+        let source = test_util::source_file("command-line");
+        let out = source.with_file_name("out.mp4");
+        let atoms = Atoms::new().title("It's me").build();
+        let mut subler = Subler::new(source.to_str().unwrap(), atoms);
+        subler.dest(out.to_str().unwrap()).optimize(false);
+        let line = subler.command_line().unwrap();
+        assert!(line.ends_with(&format!(
+            "-source {} -dest {} -metadata '{{Name:It'\\''s me}}{{Media Kind:Movie}}'",
+            source.display(),
+            out.display()
+        )));
+        assert_eq!(subler.command_line().unwrap(), line);
+        assert_eq!(subler.atoms.atoms().len(), 1);
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn validate_reports_all_problems() {
+        let source = test_util::source_file("validate");
+        let atoms = Atoms::new()
+            .add("Release Date", "03/01/2018")
+            .add("Colour", "red")
+            .build();
+        let mut subler = Subler::new(source.to_str().unwrap(), atoms);
+        match subler.validate(true).request() {
+            Err(SublerError::InvalidAtoms(problems)) => assert_eq!(problems.len(), 2),
+            other => panic!("{:?}", other),
+        }
+        match subler.validate(false).request() {
+            Err(SublerError::InvalidDate(_)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn tags_other_backends_on_the_blocking_pool() {
+        #[derive(Debug)]
+        struct Noop;
+
+        impl TagBackend for Noop {
+            fn tag(&self, request: &TagRequest) -> Result<TagOutcome> {
+                Ok(TagOutcome::new(request.dest.clone()))
+            }
+        }
+
+        let source = test_util::source_file("tag-async");
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        subler.backend(Noop);
+        let outcome = tokio::spawn(async move { subler.tag_async().await })
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome.dest, source.with_file_name("demo.0.mp4"));
+    }
+
+    #[test]
+    fn commands_use_the_executable_of_the_backend() {
+        let source = test_util::source_file("backend-executable");
+        let executable = test_util::test_dir("backend-executable-cli").join("SublerCli");
+        let mut subler = Subler::new(source.to_str().unwrap(), Atoms::new().build());
+        subler.backend(SublerCli::with_executable(&executable));
+        assert_eq!(
+            subler.build_tag_command().unwrap().get_program(),
+            &executable
+        );
+        for line in subler.dry_run().unwrap() {
+            assert!(line.starts_with(&format!("{} ", executable.display())));
+        }
+        match subler.spawn_tag() {
+            Err(SublerError::ExecutableNotFound(path)) => assert_eq!(path, executable),
+            other => panic!("{:?}", other),
+        }
+
+        subler.backend(NativeBackend);
+        assert_eq!(subler.cli().executable, Subler::cli_executeable());
+    }
+}
@@ -0,0 +1,385 @@
+//! Parsing and serialization of the ISO base media box structure
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{Result, SublerError};
+
+/// A four character box type
+pub(crate) type FourCC = [u8; 4];
+
+/// Boxes whose payload consists only of child boxes
+const CONTAINERS: &[&FourCC] = &[
+    b"moov", b"trak", b"mdia", b"minf", b"stbl", b"udta", b"edts", b"dinf", b"ilst",
+];
+
+/// How deep boxes may be nested, deeper files are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 32;
+
+/// Creates an `InvalidMp4` error
+pub(crate) fn invalid<T, S: Into<String>>(msg: S) -> Result<T> {
+    Err(SublerError::InvalidMp4(msg.into()))
+}
+
+/// A box that is fully loaded into memory
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Mp4Box {
+    /// The type of the box
+    pub kind: FourCC,
+    /// The payload of a leaf box, or the bytes preceding the children of a container,
+    /// like the version and flags of `meta`
+    pub data: Vec<u8>,
+    /// The child boxes, `None` for leaf boxes
+    pub children: Option<Vec<Mp4Box>>,
+}
+
+impl Mp4Box {
+    /// Creates a leaf box
+    pub fn leaf(kind: &FourCC, data: Vec<u8>) -> Self {
+        Mp4Box {
+            kind: *kind,
+            data,
+            children: None,
+        }
+    }
+
+    /// Creates a container box with the given children
+    pub fn container(kind: &FourCC, data: Vec<u8>, children: Vec<Mp4Box>) -> Self {
+        Mp4Box {
+            kind: *kind,
+            data,
+            children: Some(children),
+        }
+    }
+
+    /// Parses a box from its type and payload, descending into known containers
+    ///
+    /// `parent` is the type of the enclosing box, if any
+    pub fn parse(kind: FourCC, payload: &[u8], parent: Option<&FourCC>) -> Result<Self> {
+        Mp4Box::parse_nested(kind, payload, parent, 0)
+    }
+
+    /// Parses a box `depth` levels below the top level box
+    fn parse_nested(
+        kind: FourCC,
+        payload: &[u8],
+        parent: Option<&FourCC>,
+        depth: usize,
+    ) -> Result<Self> {
+        if depth > MAX_DEPTH {
+            return invalid(format!("boxes nested deeper than {} levels", MAX_DEPTH));
+        }
+        if CONTAINERS.contains(&&kind) || parent == Some(b"ilst") {
+            let children = parse_boxes(payload, Some(&kind), depth + 1)?;
+            return Ok(Mp4Box::container(&kind, Vec::new(), children));
+        }
+        if &kind == b"meta" {
+            // `meta` is a full box in MP4, but a plain container in QuickTime files
+            let prefix = if payload.len() >= 8 && &payload[4..8] == b"hdlr" {
+                0
+            } else {
+                4
+            };
+            if payload.len() < prefix {
+                return invalid("truncated meta box");
+            }
+            let children = parse_boxes(&payload[prefix..], Some(&kind), depth + 1)?;
+            return Ok(Mp4Box::container(
+                &kind,
+                payload[..prefix].to_vec(),
+                children,
+            ));
+        }
+        Ok(Mp4Box::leaf(&kind, payload.to_vec()))
+    }
+
+    /// The first direct child of the given type
+    pub fn child(&self, kind: &FourCC) -> Option<&Mp4Box> {
+        self.children.as_ref()?.iter().find(|b| &b.kind == kind)
+    }
+
+    /// Follows the path of box types starting at the children of this box
+    pub fn find(&self, path: &[&FourCC]) -> Option<&Mp4Box> {
+        path.iter().try_fold(self, |b, kind| b.child(kind))
+    }
+
+    /// The first direct child of the given type
+    pub fn child_mut(&mut self, kind: &FourCC) -> Option<&mut Mp4Box> {
+        self.children.as_mut()?.iter_mut().find(|b| &b.kind == kind)
+    }
+
+    /// The first direct child of the given type, created with `init` if it doesn't exist yet
+    pub fn child_or_insert<F: FnOnce() -> Mp4Box>(
+        &mut self,
+        kind: &FourCC,
+        init: F,
+    ) -> &mut Mp4Box {
+        let children = self.children.get_or_insert_with(Vec::new);
+        let index = match children.iter().position(|b| &b.kind == kind) {
+            Some(index) => index,
+            None => {
+                children.push(init());
+                children.len() - 1
+            }
+        };
+        &mut children[index]
+    }
+
+    /// Visits this box and all of its descendants
+    pub fn visit_mut<F: FnMut(&mut Mp4Box) -> Result<()>>(&mut self, f: &mut F) -> Result<()> {
+        f(self)?;
+        if let Some(children) = self.children.as_mut() {
+            for child in children {
+                child.visit_mut(f)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The total size of the serialized box, including its header
+    pub fn size(&self) -> u64 {
+        let children: u64 = self.children.iter().flatten().map(Mp4Box::size).sum();
+        let payload = self.data.len() as u64 + children;
+        if payload + 8 > u64::from(u32::MAX) {
+            payload + 16
+        } else {
+            payload + 8
+        }
+    }
+
+    /// Serializes the box including its header
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        let size = self.size();
+        if size > u64::from(u32::MAX) {
+            out.extend_from_slice(&1u32.to_be_bytes());
+            out.extend_from_slice(&self.kind);
+            out.extend_from_slice(&size.to_be_bytes());
+        } else {
+            out.extend_from_slice(&(size as u32).to_be_bytes());
+            out.extend_from_slice(&self.kind);
+        }
+        out.extend_from_slice(&self.data);
+        for child in self.children.iter().flatten() {
+            child.write_to(out);
+        }
+    }
+
+    /// Serializes the box into a new buffer
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.size() as usize);
+        self.write_to(&mut out);
+        out
+    }
+}
+
+/// Parses consecutive boxes from a buffer, `depth` levels below the top level box
+fn parse_boxes(mut data: &[u8], parent: Option<&FourCC>, depth: usize) -> Result<Vec<Mp4Box>> {
+    let mut boxes = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 8 {
+            // trailing padding, as written by some muxers
+            if data.iter().all(|b| *b == 0) {
+                break;
+            }
+            return invalid("truncated box header");
+        }
+        let mut kind = [0u8; 4];
+        kind.copy_from_slice(&data[4..8]);
+        let (header_len, size) = match be_u32(&data[0..4]) {
+            0 => (8, data.len() as u64),
+            1 => {
+                if data.len() < 16 {
+                    return invalid("truncated box header");
+                }
+                (16, be_u64(&data[8..16]))
+            }
+            size => (8, u64::from(size)),
+        };
+        if size < header_len as u64 || size > data.len() as u64 {
+            return invalid(format!("invalid size of box {}", fourcc_str(&kind)));
+        }
+        let size = size as usize;
+        boxes.push(Mp4Box::parse_nested(
+            kind,
+            &data[header_len..size],
+            parent,
+            depth,
+        )?);
+        data = &data[size..];
+    }
+    Ok(boxes)
+}
+
+/// The location of a top level box within a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct BoxPosition {
+    /// The type of the box
+    pub kind: FourCC,
+    /// The offset of the box header within the file
+    pub offset: u64,
+    /// The total size of the box including its header
+    pub size: u64,
+    /// The size of the box header
+    pub header_len: u64,
+}
+
+/// Scans the top level boxes of a file without reading their payloads
+pub(crate) fn scan_top_level(file: &mut File) -> Result<Vec<BoxPosition>> {
+    let len = file.metadata()?.len();
+    let mut positions = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        if len - offset < 8 {
+            return invalid("truncated top level box");
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8])?;
+        let mut kind = [0u8; 4];
+        kind.copy_from_slice(&header[4..8]);
+        let (header_len, size) = match be_u32(&header[0..4]) {
+            0 => (8, len - offset),
+            1 => {
+                file.read_exact(&mut header[8..16])?;
+                (16, be_u64(&header[8..16]))
+            }
+            size => (8, u64::from(size)),
+        };
+        if size < header_len || offset + size > len {
+            return invalid(format!("invalid size of box {}", fourcc_str(&kind)));
+        }
+        positions.push(BoxPosition {
+            kind,
+            offset,
+            size,
+            header_len,
+        });
+        offset += size;
+    }
+    Ok(positions)
+}
+
+/// Reads a top level box and all of its children into memory
+pub(crate) fn read_box(file: &mut File, position: &BoxPosition) -> Result<Mp4Box> {
+    file.seek(SeekFrom::Start(position.offset + position.header_len))?;
+    let mut payload = vec![0u8; (position.size - position.header_len) as usize];
+    file.read_exact(&mut payload)?;
+    Mp4Box::parse(position.kind, &payload, None)
+}
+
+/// Reads the `moov` box of a file
+pub(crate) fn read_moov(file: &mut File) -> Result<(Vec<BoxPosition>, Mp4Box)> {
+    let positions = scan_top_level(file)?;
+    let moov = match positions.iter().find(|p| &p.kind == b"moov") {
+        Some(position) => read_box(file, position)?,
+        None => return invalid("no moov box found"),
+    };
+    Ok((positions, moov))
+}
+
+/// Renders a box type for messages
+pub(crate) fn fourcc_str(kind: &FourCC) -> String {
+    kind.iter().map(|b| char::from(*b)).collect()
+}
+
+pub(crate) fn be_u16(data: &[u8]) -> u16 {
+    u16::from(data[0]) << 8 | u16::from(data[1])
+}
+
+pub(crate) fn be_u32(data: &[u8]) -> u32 {
+    (u32::from(be_u16(&data[0..2])) << 16) | u32::from(be_u16(&data[2..4]))
+}
+
+pub(crate) fn be_u64(data: &[u8]) -> u64 {
+    (u64::from(be_u32(&data[0..4])) << 32) | u64::from(be_u32(&data[4..8]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::bx;
+
+    /// The types of the children of a box
+    fn kinds(b: &Mp4Box) -> Vec<FourCC> {
+        b.children
+            .iter()
+            .flatten()
+            .map(|child| child.kind)
+            .collect()
+    }
+
+    /// `levels` empty `udta` boxes nested into each other, only the headers are written
+    fn nested_udta(levels: usize) -> Vec<u8> {
+        (0..levels)
+            .flat_map(|level| {
+                let mut header = (((levels - level) * 8) as u32).to_be_bytes().to_vec();
+                header.extend_from_slice(b"udta");
+                header
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rejects_deeply_nested_boxes() {
+        let payload = nested_udta(200_000);
+        match Mp4Box::parse(*b"moov", &payload, None) {
+            Err(SublerError::InvalidMp4(_)) => {}
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+        assert!(Mp4Box::parse(*b"moov", &nested_udta(MAX_DEPTH), None).is_ok());
+    }
+
+    #[test]
+    fn parses_meta_as_full_box_and_as_quicktime_container() {
+        let hdlr = bx(b"hdlr", &[0; 25]);
+        let ilst = bx(
+            b"ilst",
+            &bx(b"\xa9nam", &bx(b"data", b"\0\0\0\x01\0\0\0\0Foo")),
+        );
+        let full = [vec![0; 4], hdlr.clone(), ilst.clone()].concat();
+        let quicktime = [hdlr, ilst].concat();
+        for (payload, prefix) in &[(full, 4), (quicktime, 0)] {
+            let meta = Mp4Box::parse(*b"meta", payload, Some(b"udta")).unwrap();
+            assert_eq!(meta.data.len(), *prefix);
+            assert_eq!(kinds(&meta), vec![*b"hdlr", *b"ilst"]);
+            // `ilst` items are containers of their `data` boxes
+            let name = &meta.child(b"ilst").unwrap().children.as_ref().unwrap()[0];
+            assert_eq!(kinds(name), vec![*b"data"]);
+            assert_eq!(&meta.to_bytes()[8..], &payload[..]);
+        }
+    }
+
+    #[test]
+    fn parses_64_bit_and_open_ended_sizes() {
+        let mut large = 1u32.to_be_bytes().to_vec();
+        large.extend_from_slice(b"free");
+        large.extend_from_slice(&19u64.to_be_bytes());
+        large.extend_from_slice(b"abc");
+        let mut open_ended = 0u32.to_be_bytes().to_vec();
+        open_ended.extend_from_slice(b"skip");
+        open_ended.extend_from_slice(b"rest");
+        let moov = Mp4Box::parse(*b"moov", &[large, open_ended].concat(), None).unwrap();
+        assert_eq!(kinds(&moov), vec![*b"free", *b"skip"]);
+        assert_eq!(moov.child(b"free").unwrap().data, b"abc");
+        assert_eq!(moov.child(b"skip").unwrap().data, b"rest");
+    }
+
+    #[test]
+    fn skips_trailing_padding_only() {
+        let payload = [bx(b"free", b""), vec![0; 4]].concat();
+        assert_eq!(
+            kinds(&Mp4Box::parse(*b"udta", &payload, None).unwrap()),
+            vec![*b"free"]
+        );
+
+        let truncated = [bx(b"free", b""), vec![0, 0, 1]].concat();
+        let oversized = [100u32.to_be_bytes().to_vec(), b"free".to_vec()].concat();
+        let undersized = [4u32.to_be_bytes().to_vec(), b"free".to_vec()].concat();
+        for payload in &[truncated, oversized, undersized] {
+            match Mp4Box::parse(*b"udta", payload, None) {
+                Err(SublerError::InvalidMp4(_)) => {}
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+}
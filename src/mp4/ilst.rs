@@ -0,0 +1,733 @@
+//! Mapping between the items of the iTunes metadata list (`ilst`) and the tag names of `Atoms`
+
+use crate::mp4::boxes::{be_u16, be_u32, FourCC, Mp4Box};
+use crate::mp4::{CoverArt, ImageFormat};
+use crate::{Artwork, Atom, AtomValue, Atoms, MediaKind, Result, SublerError};
+
+/// How the payload of an item is interpreted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ItemKind {
+    /// UTF-8 text
+    Text,
+    /// A big endian integer of the given byte width
+    Integer(usize),
+    /// A single byte flag
+    Boolean,
+    /// A number-of-total pair like `trkn` and `disk`
+    Pair,
+    /// Cover art images
+    Image,
+    /// The `stik` media kind code
+    MediaKind,
+    /// An ID3v1 genre index
+    Id3Genre,
+}
+
+/// All `ilst` items that map to a tag name
+pub(crate) const ITEMS: &[(&FourCC, &str, ItemKind)] = &[
+    (b"\xa9ART", "Artist", ItemKind::Text),
+    (b"aART", "Album Artist", ItemKind::Text),
+    (b"\xa9alb", "Album", ItemKind::Text),
+    (b"\xa9grp", "Grouping", ItemKind::Text),
+    (b"\xa9wrt", "Composer", ItemKind::Text),
+    (b"\xa9cmt", "Comments", ItemKind::Text),
+    (b"\xa9gen", "Genre", ItemKind::Text),
+    (b"gnre", "Genre", ItemKind::Id3Genre),
+    (b"\xa9day", "Release Date", ItemKind::Text),
+    (b"trkn", "Track #", ItemKind::Pair),
+    (b"disk", "Disk #", ItemKind::Pair),
+    (b"tmpo", "Tempo", ItemKind::Integer(2)),
+    (b"tvsh", "TV Show", ItemKind::Text),
+    (b"tves", "TV Episode #", ItemKind::Integer(4)),
+    (b"tvnn", "TV Network", ItemKind::Text),
+    (b"tven", "TV Episode ID", ItemKind::Text),
+    (b"tvsn", "TV Season", ItemKind::Integer(4)),
+    (b"desc", "Description", ItemKind::Text),
+    (b"ldes", "Long Description", ItemKind::Text),
+    (b"sdes", "Series Description", ItemKind::Text),
+    (b"hdvd", "HD Video", ItemKind::Boolean),
+    (b"\xa9dir", "Director", ItemKind::Text),
+    (b"pgap", "Gapless", ItemKind::Boolean),
+    (b"\xa9lyr", "Lyrics", ItemKind::Text),
+    (b"cprt", "Copyright", ItemKind::Text),
+    (b"\xa9too", "Encoding Tool", ItemKind::Text),
+    (b"\xa9enc", "Encoded By", ItemKind::Text),
+    (b"keyw", "Keywords", ItemKind::Text),
+    (b"catg", "Category", ItemKind::Text),
+    (b"cnID", "contentID", ItemKind::Integer(4)),
+    (b"atID", "artistID", ItemKind::Integer(4)),
+    (b"plID", "playlistID", ItemKind::Integer(8)),
+    (b"geID", "genreID", ItemKind::Integer(4)),
+    (b"cmID", "composerID", ItemKind::Integer(4)),
+    (b"xid ", "XID", ItemKind::Text),
+    (b"apID", "iTunes Account", ItemKind::Text),
+    (b"akID", "iTunes Account Type", ItemKind::Integer(1)),
+    (b"sfID", "iTunes Country", ItemKind::Integer(4)),
+    (b"\xa9st3", "Track Sub-Title", ItemKind::Text),
+    (b"\xa9des", "Song Description", ItemKind::Text),
+    (b"\xa9ard", "Art Director", ItemKind::Text),
+    (b"\xa9arg", "Arranger", ItemKind::Text),
+    (b"\xa9aut", "Lyricist", ItemKind::Text),
+    (b"\xa9cak", "Acknowledgement", ItemKind::Text),
+    (b"\xa9con", "Conductor", ItemKind::Text),
+    (b"\xa9lnt", "Linear Notes", ItemKind::Text),
+    (b"\xa9mak", "Record Company", ItemKind::Text),
+    (b"\xa9ope", "Original Artist", ItemKind::Text),
+    (b"\xa9phg", "Phonogram Rights", ItemKind::Text),
+    (b"\xa9prd", "Producer", ItemKind::Text),
+    (b"\xa9prf", "Performer", ItemKind::Text),
+    (b"\xa9pub", "Publisher", ItemKind::Text),
+    (b"\xa9sne", "Sound Engineer", ItemKind::Text),
+    (b"\xa9sol", "Soloist", ItemKind::Text),
+    (b"\xa9src", "Credits", ItemKind::Text),
+    (b"\xa9thx", "Thanks", ItemKind::Text),
+    (b"\xa9url", "Online Extras", ItemKind::Text),
+    (b"\xa9xpd", "Executive Producer", ItemKind::Text),
+    (b"sonm", "Sort Name", ItemKind::Text),
+    (b"soar", "Sort Artist", ItemKind::Text),
+    (b"soaa", "Sort Album Artist", ItemKind::Text),
+    (b"soal", "Sort Album", ItemKind::Text),
+    (b"soco", "Sort Composer", ItemKind::Text),
+    (b"sosn", "Sort TV Show", ItemKind::Text),
+    (b"covr", "Artwork", ItemKind::Image),
+    (b"\xa9nam", "Name", ItemKind::Text),
+    (b"stik", "Media Kind", ItemKind::MediaKind),
+];
+
+/// The mean of all freeform items written by iTunes
+pub(crate) const ITUNES_MEAN: &str = "com.apple.iTunes";
+
+/// Tag names stored in the `iTunMOVI` property list, with their plist keys
+pub(crate) const MOVI_KEYS: &[(&str, &str)] = &[
+    ("Cast", "cast"),
+    ("Director", "directors"),
+    ("Codirector", "codirectors"),
+    ("Producers", "producers"),
+    ("Screenwriters", "screenwriters"),
+    ("Studio", "studio"),
+];
+
+/// The ID3v1 genres, as referenced by `gnre` items
+#[rustfmt::skip]
+const ID3_GENRES: &[&str] = &[
+    "Blues", "Classic Rock", "Country", "Dance", "Disco", "Funk", "Grunge", "Hip-Hop", "Jazz",
+    "Metal", "New Age", "Oldies", "Other", "Pop", "R&B", "Rap", "Reggae", "Rock", "Techno",
+    "Industrial", "Alternative", "Ska", "Death Metal", "Pranks", "Soundtrack", "Euro-Techno",
+    "Ambient", "Trip-Hop", "Vocal", "Jazz+Funk", "Fusion", "Trance", "Classical", "Instrumental",
+    "Acid", "House", "Game", "Sound Clip", "Gospel", "Noise", "AlternRock", "Bass", "Soul",
+    "Punk", "Space", "Meditative", "Instrumental Pop", "Instrumental Rock", "Ethnic", "Gothic",
+    "Darkwave", "Techno-Industrial", "Electronic", "Pop-Folk", "Eurodance", "Dream",
+    "Southern Rock", "Comedy", "Cult", "Gangsta", "Top 40", "Christian Rap", "Pop/Funk", "Jungle",
+    "Native American", "Cabaret", "New Wave", "Psychadelic", "Rave", "Showtunes", "Trailer",
+    "Lo-Fi", "Tribal", "Acid Punk", "Acid Jazz", "Polka", "Retro", "Musical", "Rock & Roll",
+    "Hard Rock",
+];
+
+/// Well known data type codes of `data` boxes
+pub(crate) const TYPE_IMPLICIT: u32 = 0;
+pub(crate) const TYPE_UTF8: u32 = 1;
+pub(crate) const TYPE_JPEG: u32 = 13;
+pub(crate) const TYPE_PNG: u32 = 14;
+pub(crate) const TYPE_SIGNED: u32 = 21;
+pub(crate) const TYPE_UNSIGNED: u32 = 22;
+pub(crate) const TYPE_BMP: u32 = 27;
+
+/// A decoded `data` box
+struct Data<'a> {
+    type_code: u32,
+    payload: &'a [u8],
+}
+
+/// All `data` boxes of an item
+fn data_of(item: &Mp4Box) -> Vec<Data<'_>> {
+    item.children
+        .iter()
+        .flatten()
+        .filter(|b| &b.kind == b"data" && b.data.len() >= 8)
+        .map(|b| Data {
+            type_code: be_u32(&b.data[0..4]) & 0x00ff_ffff,
+            payload: &b.data[8..],
+        })
+        .collect()
+}
+
+/// The text payload of the first `mean`/`name` box of a freeform item
+fn freeform_field<'a>(item: &'a Mp4Box, kind: &FourCC) -> Option<&'a [u8]> {
+    let field = item.child(kind)?;
+    field.data.get(4..)
+}
+
+/// Decodes a big endian integer of any width up to 8 bytes
+fn decode_integer(payload: &[u8], signed: bool) -> Option<i64> {
+    if payload.is_empty() || payload.len() > 8 {
+        return None;
+    }
+    let mut value = payload.iter().fold(0u64, |acc, b| acc << 8 | u64::from(*b));
+    let bits = payload.len() * 8;
+    if signed && bits < 64 && payload[0] & 0x80 != 0 {
+        value |= !0u64 << bits;
+    }
+    Some(value as i64)
+}
+
+/// Converts an item into the atoms it represents
+pub(crate) fn item_to_atoms(item: &Mp4Box) -> Vec<Atom> {
+    if &item.kind == b"----" {
+        return freeform_to_atoms(item);
+    }
+    let (tag, kind) = match ITEMS.iter().find(|(fourcc, _, _)| **fourcc == item.kind) {
+        Some((_, tag, kind)) => (*tag, *kind),
+        None => return Vec::new(),
+    };
+    let data = data_of(item);
+    let first = match data.first() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    let value = match kind {
+        ItemKind::Text => Some(String::from_utf8_lossy(first.payload).into_owned()),
+        ItemKind::Integer(_) => {
+            decode_integer(first.payload, first.type_code != TYPE_UNSIGNED).map(|v| v.to_string())
+        }
+        ItemKind::Boolean => decode_integer(first.payload, false).map(|v| {
+            if v != 0 {
+                "1".to_owned()
+            } else {
+                "0".to_owned()
+            }
+        }),
+        ItemKind::Pair if first.payload.len() >= 6 => {
+            let number = be_u16(&first.payload[2..4]);
+            match be_u16(&first.payload[4..6]) {
+                0 => Some(number.to_string()),
+                total => Some(format!("{}/{}", number, total)),
+            }
+        }
+        ItemKind::Pair | ItemKind::Image => None,
+        ItemKind::MediaKind => decode_integer(first.payload, false)
+            .and_then(|code| MediaKind::from_stik(code as u8))
+            .map(|kind| kind.as_str().to_owned()),
+        ItemKind::Id3Genre => decode_integer(first.payload, false)
+            .and_then(|index| ID3_GENRES.get((index as usize).checked_sub(1)?))
+            .map(|genre| (*genre).to_owned()),
+    };
+    value
+        .map(|v| vec![Atom::parse(tag, &v)])
+        .unwrap_or_default()
+}
+
+/// Converts the `----` items written by iTunes into atoms
+fn freeform_to_atoms(item: &Mp4Box) -> Vec<Atom> {
+    if freeform_field(item, b"mean") != Some(ITUNES_MEAN.as_bytes()) {
+        return Vec::new();
+    }
+    let name = freeform_field(item, b"name").unwrap_or_default();
+    let value = match data_of(item).first() {
+        Some(data) => String::from_utf8_lossy(data.payload).into_owned(),
+        None => return Vec::new(),
+    };
+    match name {
+        b"iTunEXTC" => {
+            // system|label|code|annotation
+            let fields: Vec<&str> = value.splitn(4, '|').collect();
+            let mut atoms = Vec::new();
+            if fields.len() >= 3 {
+                atoms.push(Atom::new(
+                    "Rating",
+                    &format!("{}|{}|{}|", fields[0], fields[1], fields[2]),
+                ));
+            }
+            if let Some(annotation) = fields.get(3).filter(|a| !a.is_empty()) {
+                atoms.push(Atom::new("Rating Annotation", annotation));
+            }
+            atoms
+        }
+        b"iTunMOVI" => MOVI_KEYS
+            .iter()
+            .filter_map(|(tag, key)| {
+                let values = plist_values(&value, key);
+                if values.is_empty() {
+                    None
+                } else {
+                    Some(Atom::new(tag, &values.join(", ")))
+                }
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Extracts the images of a `covr` item
+pub(crate) fn item_to_artwork(item: &Mp4Box) -> Vec<CoverArt> {
+    if &item.kind != b"covr" {
+        return Vec::new();
+    }
+    data_of(item)
+        .into_iter()
+        .filter_map(|data| {
+            let format = match data.type_code {
+                TYPE_JPEG => ImageFormat::Jpeg,
+                TYPE_PNG => ImageFormat::Png,
+                TYPE_BMP => ImageFormat::Bmp,
+                TYPE_IMPLICIT => ImageFormat::sniff(data.payload)?,
+                _ => return None,
+            };
+            Some(CoverArt {
+                format,
+                data: data.payload.to_vec(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the values stored under `key` in an `iTunMOVI` property list.
+///
+/// The key either maps to a single `<string>` or to an `<array>` of dicts with a `name` entry.
+fn plist_values(plist: &str, key: &str) -> Vec<String> {
+    let marker = format!("<key>{}</key>", key);
+    let rest = match plist.find(&marker) {
+        Some(pos) => plist[pos + marker.len()..].trim_start(),
+        None => return Vec::new(),
+    };
+    if rest.starts_with("<string>") {
+        return element_text(rest, "string")
+            .map(|s| vec![xml_unescape(s)])
+            .unwrap_or_default();
+    }
+    if !rest.starts_with("<array>") {
+        return Vec::new();
+    }
+    let array = match rest.find("</array>") {
+        Some(end) => &rest[..end],
+        None => return Vec::new(),
+    };
+    array
+        .split("<key>name</key>")
+        .skip(1)
+        .filter_map(|entry| element_text(entry.trim_start(), "string"))
+        .map(xml_unescape)
+        .collect()
+}
+
+/// The text of the first `<name>` element in `xml`
+fn element_text<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    Some(&xml[start..end])
+}
+
+/// Resolves the predefined XML entities
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Builds the `ilst` box of a file from its `existing` items with every tag in `atoms` replaced
+///
+/// Deletion atoms remove their tag. Tags that can't be stored in an `ilst` are ignored,
+/// like SublerCli does.
+pub(crate) fn build_ilst(existing: Option<&Mp4Box>, atoms: &Atoms) -> Result<Mp4Box> {
+    let mut items: Vec<Mp4Box> = existing
+        .and_then(|ilst| ilst.children.clone())
+        .unwrap_or_default();
+    let existing_atoms: Vec<Atom> = items.iter().flat_map(item_to_atoms).collect();
+    let mut new_items: Vec<Mp4Box> = Vec::new();
+    let mut covers: Vec<Mp4Box> = Vec::new();
+    let mut rating: Option<String> = None;
+    let mut annotation: Option<String> = None;
+    let mut movi: Vec<(&str, String)> = Vec::new();
+
+    for atom in atoms.atoms() {
+        let tag = atom.tag.as_str();
+        if let Some((fourcc, kind)) = item_for_tag(tag) {
+            items.retain(|item| !ITEMS.iter().any(|(f, t, _)| **f == item.kind && *t == tag));
+            if kind == ItemKind::Image {
+                if atom.is_deletion() {
+                    covers.clear();
+                } else {
+                    covers.push(encode_image(atom)?);
+                }
+            } else {
+                new_items.retain(|item| &item.kind != fourcc);
+                if !atom.is_deletion() {
+                    let data = encode(kind, atom)?;
+                    new_items.push(Mp4Box::container(fourcc, Vec::new(), vec![data]));
+                }
+            }
+        } else if tag == "Rating" {
+            rating = Some(atom.value.to_string());
+        } else if tag == "Rating Annotation" {
+            annotation = Some(atom.value.to_string());
+        } else if let Some((movi_tag, _)) = MOVI_KEYS.iter().find(|(t, _)| *t == tag) {
+            movi.retain(|(t, _)| t != movi_tag);
+            movi.push((movi_tag, atom.value.to_string()));
+        }
+    }
+    if !covers.is_empty() {
+        new_items.push(Mp4Box::container(b"covr", Vec::new(), covers));
+    }
+
+    let existing_value = |tag: &str| {
+        existing_atoms
+            .iter()
+            .find(|a| a.tag == tag)
+            .map(|a| a.value.to_string())
+    };
+    if rating.is_some() || annotation.is_some() {
+        let rating = rating.or_else(|| existing_value("Rating"));
+        let annotation = annotation.or_else(|| existing_value("Rating Annotation"));
+        items.retain(|item| !is_freeform(item, "iTunEXTC"));
+        if let Some(rating) = rating.filter(|r| !r.is_empty()) {
+            let fields: Vec<&str> = rating.split('|').take(3).collect();
+            if fields.len() < 3 {
+                return Err(SublerError::InvalidAtomValue {
+                    tag: "Rating".to_owned(),
+                    value: rating.clone(),
+                });
+            }
+            let extc = format!("{}|{}", fields.join("|"), annotation.unwrap_or_default());
+            new_items.push(freeform("iTunEXTC", &extc));
+        }
+    }
+    if !movi.is_empty() {
+        let mut entries: Vec<(&str, String)> = Vec::new();
+        for (tag, key) in MOVI_KEYS {
+            let value = movi
+                .iter()
+                .find(|(t, _)| t == tag)
+                .map(|(_, v)| v.clone())
+                .or_else(|| existing_value(tag));
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                entries.push((key, value));
+            }
+        }
+        items.retain(|item| !is_freeform(item, "iTunMOVI"));
+        if !entries.is_empty() {
+            new_items.push(freeform("iTunMOVI", &movi_plist(&entries)));
+        }
+    }
+
+    items.extend(new_items);
+    Ok(Mp4Box::container(b"ilst", Vec::new(), items))
+}
+
+/// The item and kind a tag is written to
+fn item_for_tag(tag: &str) -> Option<(&'static FourCC, ItemKind)> {
+    ITEMS
+        .iter()
+        .find(|(_, t, kind)| *t == tag && *kind != ItemKind::Id3Genre)
+        .map(|(fourcc, _, kind)| (*fourcc, *kind))
+}
+
+/// Whether the number can be stored as a signed integer of `width` bytes
+fn fits_integer(number: i64, width: usize) -> bool {
+    let bits = width as u32 * 8;
+    bits >= 64 || (-(1i64 << (bits - 1))..(1i64 << (bits - 1))).contains(&number)
+}
+
+/// Whether the number can be stored in the integer item of the tag, tags of other items
+/// are not limited
+pub(crate) fn integer_fits_tag(tag: &str, number: i64) -> bool {
+    match item_for_tag(tag) {
+        Some((_, ItemKind::Integer(width))) => fits_integer(number, width),
+        _ => true,
+    }
+}
+
+/// Whether the item is the iTunes freeform item with the given name
+fn is_freeform(item: &Mp4Box, name: &str) -> bool {
+    &item.kind == b"----"
+        && freeform_field(item, b"mean") == Some(ITUNES_MEAN.as_bytes())
+        && freeform_field(item, b"name") == Some(name.as_bytes())
+}
+
+/// Creates a `data` box
+fn data_box(type_code: u32, payload: &[u8]) -> Mp4Box {
+    let mut data = Vec::with_capacity(payload.len() + 8);
+    data.extend_from_slice(&type_code.to_be_bytes());
+    data.extend_from_slice(&[0; 4]);
+    data.extend_from_slice(payload);
+    Mp4Box::leaf(b"data", data)
+}
+
+/// Creates an iTunes freeform item holding text
+fn freeform(name: &str, value: &str) -> Mp4Box {
+    let field = |kind: &FourCC, text: &str| {
+        let mut data = vec![0; 4];
+        data.extend_from_slice(text.as_bytes());
+        Mp4Box::leaf(kind, data)
+    };
+    Mp4Box::container(
+        b"----",
+        Vec::new(),
+        vec![
+            field(b"mean", ITUNES_MEAN),
+            field(b"name", name),
+            data_box(TYPE_UTF8, value.as_bytes()),
+        ],
+    )
+}
+
+/// Encodes the value of an atom into a `data` box
+fn encode(kind: ItemKind, atom: &Atom) -> Result<Mp4Box> {
+    let text = atom.value.to_string();
+    let invalid = || SublerError::InvalidAtomValue {
+        tag: atom.tag.clone(),
+        value: text.clone(),
+    };
+    let value = text.trim();
+    match kind {
+        ItemKind::Text => Ok(data_box(TYPE_UTF8, text.as_bytes())),
+        ItemKind::Integer(width) => {
+            let number: i64 = value.parse().map_err(|_| invalid())?;
+            if !fits_integer(number, width) {
+                return Err(invalid());
+            }
+            Ok(data_box(TYPE_SIGNED, &number.to_be_bytes()[8 - width..]))
+        }
+        ItemKind::Boolean => {
+            let flag = match value.to_lowercase().as_str() {
+                "1" | "yes" | "true" => 1,
+                "0" | "no" | "false" => 0,
+                _ => return Err(invalid()),
+            };
+            Ok(data_box(TYPE_SIGNED, &[flag]))
+        }
+        ItemKind::Pair => {
+            let mut parts = value.splitn(2, '/').map(str::trim);
+            let number: u16 = parts
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(invalid)?;
+            let total: u16 = match parts.next() {
+                Some(total) => total.parse().map_err(|_| invalid())?,
+                None => 0,
+            };
+            let mut payload = vec![0, 0];
+            payload.extend_from_slice(&number.to_be_bytes());
+            payload.extend_from_slice(&total.to_be_bytes());
+            if atom.tag == "Track #" {
+                payload.extend_from_slice(&[0, 0]);
+            }
+            Ok(data_box(TYPE_IMPLICIT, &payload))
+        }
+        ItemKind::MediaKind => {
+            let kind = MediaKind::from_name(value).ok_or_else(invalid)?;
+            Ok(data_box(TYPE_SIGNED, &[kind.stik()]))
+        }
+        ItemKind::Image => encode_image(atom),
+        ItemKind::Id3Genre => Err(invalid()),
+    }
+}
+
+/// Reads the image of an `Artwork` atom
+fn encode_image(atom: &Atom) -> Result<Mp4Box> {
+    let image = match &atom.value {
+        AtomValue::Image(artwork) => artwork.load()?,
+        value => Artwork::from_path(value.to_string()).load()?,
+    };
+    let type_code = match image.format {
+        ImageFormat::Jpeg => TYPE_JPEG,
+        ImageFormat::Png => TYPE_PNG,
+        ImageFormat::Bmp => TYPE_BMP,
+    };
+    Ok(data_box(type_code, &image.data))
+}
+
+/// Renders the `iTunMOVI` property list for the given plist keys and values
+fn movi_plist(entries: &[(&str, String)]) -> String {
+    let mut plist = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+        "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\">\n<dict>\n"
+    ));
+    for (key, value) in entries {
+        plist.push_str(&format!("\t<key>{}</key>\n", key));
+        if *key == "studio" {
+            plist.push_str(&format!("\t<string>{}</string>\n", xml_escape(value)));
+            continue;
+        }
+        plist.push_str("\t<array>\n");
+        for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            plist.push_str(&format!(
+                "\t\t<dict>\n\t\t\t<key>name</key>\n\t\t\t<string>{}</string>\n\t\t</dict>\n",
+                xml_escape(name)
+            ));
+        }
+        plist.push_str("\t</array>\n");
+    }
+    plist.push_str("</dict>\n</plist>\n");
+    plist
+}
+
+/// Escapes the characters that are not allowed in XML text
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An item of `kind` with a single `data` box
+    fn item(kind: &FourCC, type_code: u32, payload: &[u8]) -> Mp4Box {
+        Mp4Box::container(kind, Vec::new(), vec![data_box(type_code, payload)])
+    }
+
+    /// The tags and values of the atoms an item is read as
+    fn read(item: &Mp4Box) -> Vec<(String, String)> {
+        item_to_atoms(item)
+            .into_iter()
+            .map(|atom| (atom.tag, atom.value.to_string()))
+            .collect()
+    }
+
+    /// Writes the atoms into a new `ilst` and reads them back
+    fn round_trip(atoms: &Atoms) -> Vec<(String, String)> {
+        let ilst = build_ilst(None, atoms).unwrap();
+        ilst.children.iter().flatten().flat_map(read).collect()
+    }
+
+    fn pair(tag: &str, value: &str) -> (String, String) {
+        (tag.to_owned(), value.to_owned())
+    }
+
+    #[test]
+    fn decodes_integers_by_their_type() {
+        assert_eq!(
+            read(&item(b"tmpo", TYPE_SIGNED, &[0xff, 0xfe])),
+            vec![pair("Tempo", "-2")]
+        );
+        assert_eq!(
+            read(&item(b"tmpo", TYPE_UNSIGNED, &[0xff, 0xfe])),
+            vec![pair("Tempo", "65534")]
+        );
+        assert_eq!(
+            read(&item(b"plID", TYPE_SIGNED, &[0xff; 8])),
+            vec![pair("playlistID", "-1")]
+        );
+        // empty and overlong payloads are skipped
+        assert!(read(&item(b"tmpo", TYPE_SIGNED, &[])).is_empty());
+        assert!(read(&item(b"tmpo", TYPE_SIGNED, &[0; 9])).is_empty());
+    }
+
+    #[test]
+    fn skips_unknown_and_empty_items() {
+        assert!(read(&item(b"zzzz", TYPE_UTF8, b"Foo")).is_empty());
+        assert!(read(&Mp4Box::container(b"\xa9nam", Vec::new(), Vec::new())).is_empty());
+        // a `data` box without its type and locale
+        let short = Mp4Box::leaf(b"data", vec![0, 0, 0, 1]);
+        assert!(read(&Mp4Box::container(b"\xa9nam", Vec::new(), vec![short])).is_empty());
+    }
+
+    #[test]
+    fn reads_id3_genres_from_one() {
+        assert_eq!(
+            read(&item(b"gnre", TYPE_IMPLICIT, &[0, 1])),
+            vec![pair("Genre", "Blues")]
+        );
+        assert!(read(&item(b"gnre", TYPE_IMPLICIT, &[0, 0])).is_empty());
+        assert!(read(&item(b"gnre", TYPE_IMPLICIT, &[0, 200])).is_empty());
+    }
+
+    #[test]
+    fn encodes_pairs_with_their_item_layout() {
+        let atoms = Atoms::new()
+            .add("Track #", "3/12")
+            .add("Disk #", "1")
+            .build();
+        let ilst = build_ilst(None, &atoms).unwrap();
+        let lengths: Vec<_> = ilst
+            .children
+            .iter()
+            .flatten()
+            .map(|item| data_of(item)[0].payload.len())
+            .collect();
+        assert_eq!(lengths, vec![8, 6]);
+        assert_eq!(
+            round_trip(&atoms),
+            vec![pair("Track #", "3/12"), pair("Disk #", "1")]
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_dont_fit_their_item() {
+        for (tag, value) in &[
+            ("HD Video", "maybe"),
+            ("Track #", "3/x"),
+            ("Track #", "70000"),
+            ("TV Season", "4294967296"),
+            ("Media Kind", "Hologram"),
+        ] {
+            match build_ilst(None, &Atoms::new().add(tag, value).build()) {
+                Err(SublerError::InvalidAtomValue { tag: t, value: v }) => {
+                    assert_eq!((t.as_str(), v.as_str()), (*tag, *value))
+                }
+                other => panic!("{} {}: {:?}", tag, value, other.map(|_| ())),
+            }
+        }
+        let flags = Atoms::new()
+            .add("HD Video", "Yes")
+            .add("Gapless", "false")
+            .build();
+        assert_eq!(
+            round_trip(&flags),
+            vec![pair("HD Video", "1"), pair("Gapless", "0")]
+        );
+    }
+
+    #[test]
+    fn keeps_ratings_and_their_annotation_together() {
+        let atoms = Atoms::new()
+            .add("Rating", "us-tv|TV-MA|600|")
+            .add("Rating Annotation", "Violence")
+            .build();
+        let ilst = build_ilst(None, &atoms).unwrap();
+        let extc = &ilst.children.as_ref().unwrap()[0];
+        assert_eq!(data_of(extc)[0].payload, b"us-tv|TV-MA|600|Violence");
+
+        // a new annotation keeps the existing rating
+        let annotation = Atoms::new().add("Rating Annotation", "Language").build();
+        let ilst = build_ilst(Some(&ilst), &annotation).unwrap();
+        let items: Vec<_> = ilst.children.iter().flatten().flat_map(read).collect();
+        assert_eq!(
+            items,
+            vec![
+                pair("Rating", "us-tv|TV-MA|600|"),
+                pair("Rating Annotation", "Language")
+            ]
+        );
+
+        match build_ilst(None, &Atoms::new().add("Rating", "TV-MA").build()) {
+            Err(SublerError::InvalidAtomValue { tag, .. }) => assert_eq!(tag, "Rating"),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn escapes_the_movie_property_list() {
+        let atoms = Atoms::new()
+            .add("Cast", "Tom & Jerry, <Anon>, ")
+            .add("Studio", "\"A\" & 'B'")
+            .build();
+        assert_eq!(
+            round_trip(&atoms),
+            vec![
+                pair("Cast", "Tom & Jerry, <Anon>"),
+                pair("Studio", "\"A\" & 'B'")
+            ]
+        );
+        // freeform items of other applications are ignored
+        let mut foreign = freeform("iTunMOVI", "<key>cast</key><string>X</string>");
+        foreign.children.as_mut().unwrap()[0].data = b"\0\0\0\0org.example".to_vec();
+        assert!(read(&foreign).is_empty());
+    }
+}
@@ -0,0 +1,313 @@
+//! Rewriting files with a modified `moov` box, relocating all chunk offsets
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cancel::Abort;
+use crate::mp4::boxes::{be_u32, be_u64, invalid, scan_top_level, BoxPosition, Mp4Box};
+use crate::{Result, SublerError};
+
+/// The size of the blocks the media data is copied in, checking for an abort in between
+const COPY_BLOCK: u64 = 1 << 20;
+
+/// Where the `moov` box is placed in the rewritten file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum MoovPlacement {
+    /// At the position of the original `moov`
+    Keep,
+    /// In front of the first `mdat`, with all top level `free` space dropped,
+    /// so that playback can start before the whole file is loaded
+    BeforeMdat,
+}
+
+/// Maps offsets of the original file to offsets in the rewritten file
+struct Relocation {
+    /// `(old start, old end, new start)` of every moved top level box
+    ranges: Vec<(u64, u64, u64)>,
+}
+
+impl Relocation {
+    fn map(&self, offset: u64) -> u64 {
+        self.ranges
+            .iter()
+            .find(|(start, end, _)| *start <= offset && offset < *end)
+            .map(|(start, _, new_start)| new_start + (offset - start))
+            .unwrap_or(offset)
+    }
+}
+
+/// Writes `source` to `dest`, with its `moov` box replaced by `moov`
+///
+/// The chunk offsets in `moov` refer to the layout of `source` and are relocated to the
+/// layout of `dest`. `stco` boxes are widened to `co64` where the offsets no longer fit.
+/// The media data itself is copied unchanged, so its interleaving is preserved.
+/// If `abort` fires while copying, nothing is written to `dest`.
+///
+/// Fragmented files are rejected with `SublerError::Unsupported`, the offsets in their
+/// `moof` and `mfra` boxes are not relocated.
+pub(crate) fn rewrite(
+    source: &Path,
+    dest: &Path,
+    mut moov: Mp4Box,
+    placement: MoovPlacement,
+    abort: &Abort,
+) -> Result<()> {
+    let mut file = File::open(source)?;
+    let positions = scan_top_level(&mut file)?;
+    let fragmented = moov.child(b"mvex").is_some()
+        || positions
+            .iter()
+            .any(|p| &p.kind == b"moof" || &p.kind == b"mfra");
+    if fragmented {
+        return Err(SublerError::Unsupported("Rewriting fragmented MP4 files"));
+    }
+    let optimize = placement == MoovPlacement::BeforeMdat;
+    let kept: Vec<&BoxPosition> = positions
+        .iter()
+        .filter(|p| !(optimize && (&p.kind == b"free" || &p.kind == b"skip")))
+        .collect();
+    let moov_index = match kept.iter().position(|p| &p.kind == b"moov") {
+        Some(index) => index,
+        None => return invalid("no moov box found"),
+    };
+    let others: Vec<BoxPosition> = kept
+        .into_iter()
+        .filter(|p| &p.kind != b"moov")
+        .cloned()
+        .collect();
+    let insert_at = match placement {
+        MoovPlacement::Keep => moov_index,
+        MoovPlacement::BeforeMdat => others
+            .iter()
+            .position(|p| &p.kind == b"mdat")
+            .map_or(moov_index, |mdat| mdat.min(moov_index)),
+    };
+
+    let moov = loop {
+        let relocation = layout(&others, insert_at, moov.size());
+        if widen_overflowing(&mut moov, &relocation)? {
+            continue;
+        }
+        let mut relocated = moov.clone();
+        relocate(&mut relocated, &relocation)?;
+        break relocated;
+    };
+
+    let tmp = temp_path(dest);
+    let result = write_layout(&mut file, &tmp, &others, insert_at, &moov, abort)
+        .and_then(|_| fs::rename(&tmp, dest).map_err(Into::into));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Computes where the boxes end up when a `moov` of `moov_size` is placed at `insert_at`
+fn layout(others: &[BoxPosition], insert_at: usize, moov_size: u64) -> Relocation {
+    let mut offset = 0;
+    let mut ranges = Vec::with_capacity(others.len());
+    for (index, position) in others.iter().enumerate() {
+        if index == insert_at {
+            offset += moov_size;
+        }
+        ranges.push((position.offset, position.offset + position.size, offset));
+        offset += position.size;
+    }
+    Relocation { ranges }
+}
+
+/// Reads the offsets of a chunk offset box
+fn chunk_offsets(b: &Mp4Box) -> Result<Vec<u64>> {
+    let wide = &b.kind == b"co64";
+    let width = if wide { 8 } else { 4 };
+    if b.data.len() < 8 {
+        return invalid("truncated chunk offset box");
+    }
+    let count = be_u32(&b.data[4..8]) as usize;
+    let entries = &b.data[8..];
+    if entries.len() < count * width {
+        return invalid("truncated chunk offset box");
+    }
+    Ok(entries
+        .chunks(width)
+        .take(count)
+        .map(|e| {
+            if wide {
+                be_u64(e)
+            } else {
+                u64::from(be_u32(e))
+            }
+        })
+        .collect())
+}
+
+/// Replaces the offsets of a chunk offset box, keeping its type
+fn set_chunk_offsets(b: &mut Mp4Box, offsets: &[u64]) {
+    let mut data = b.data[..4].to_vec();
+    data.extend_from_slice(&(offsets.len() as u32).to_be_bytes());
+    for offset in offsets {
+        if &b.kind == b"co64" {
+            data.extend_from_slice(&offset.to_be_bytes());
+        } else {
+            data.extend_from_slice(&(*offset as u32).to_be_bytes());
+        }
+    }
+    b.data = data;
+}
+
+/// Turns every `stco` whose relocated offsets exceed 32 bit into a `co64`
+///
+/// Returns whether any box was widened, which changes the size of `moov`
+fn widen_overflowing(moov: &mut Mp4Box, relocation: &Relocation) -> Result<bool> {
+    let mut widened = false;
+    moov.visit_mut(&mut |b| {
+        if &b.kind == b"stco" {
+            let offsets = chunk_offsets(b)?;
+            if offsets
+                .iter()
+                .any(|o| relocation.map(*o) > u64::from(u32::MAX))
+            {
+                b.kind = *b"co64";
+                set_chunk_offsets(b, &offsets);
+                widened = true;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(widened)
+}
+
+/// Relocates the offsets of all `stco` and `co64` boxes
+fn relocate(moov: &mut Mp4Box, relocation: &Relocation) -> Result<()> {
+    moov.visit_mut(&mut |b| {
+        if &b.kind == b"stco" || &b.kind == b"co64" {
+            let offsets: Vec<u64> = chunk_offsets(b)?
+                .into_iter()
+                .map(|o| relocation.map(o))
+                .collect();
+            set_chunk_offsets(b, &offsets);
+        }
+        Ok(())
+    })
+}
+
+/// The path the output is written to before it's moved to `dest`
+fn temp_path(dest: &Path) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dest.with_file_name(format!(".{}.tmp", name))
+}
+
+/// Writes the boxes of the source `file` in their new order to `dest`
+fn write_layout(
+    file: &mut File,
+    dest: &Path,
+    others: &[BoxPosition],
+    insert_at: usize,
+    moov: &Mp4Box,
+    abort: &Abort,
+) -> Result<()> {
+    let mut out = BufWriter::new(File::create(dest)?);
+    for (index, position) in others.iter().enumerate() {
+        if index == insert_at {
+            out.write_all(&moov.to_bytes())?;
+        }
+        file.seek(SeekFrom::Start(position.offset))?;
+        let mut remaining = position.size;
+        while remaining > 0 {
+            abort.check()?;
+            let block = remaining.min(COPY_BLOCK);
+            let copied = io::copy(&mut (&mut *file).take(block), &mut out)?;
+            if copied != block {
+                return invalid("unexpected end of file");
+            }
+            remaining -= block;
+        }
+    }
+    if insert_at >= others.len() {
+        out.write_all(&moov.to_bytes())?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mp4::boxes::FourCC;
+
+    /// A `moov` with a single `stco` holding `offsets`
+    fn moov(offsets: &[u64]) -> Mp4Box {
+        let mut stco = Mp4Box::leaf(b"stco", vec![0; 4]);
+        set_chunk_offsets(&mut stco, offsets);
+        let stbl = Mp4Box::container(b"stbl", Vec::new(), vec![stco]);
+        Mp4Box::container(b"moov", Vec::new(), vec![stbl])
+    }
+
+    fn stco(moov: &Mp4Box) -> &Mp4Box {
+        &moov.child(b"stbl").unwrap().children.as_ref().unwrap()[0]
+    }
+
+    /// A top level box of `kind` at `offset`
+    fn position(kind: &FourCC, offset: u64, size: u64) -> BoxPosition {
+        BoxPosition {
+            kind: *kind,
+            offset,
+            size,
+            header_len: 8,
+        }
+    }
+
+    #[test]
+    fn maps_offsets_of_moved_boxes() {
+        // `ftyp`, `moov` of 100 bytes and `mdat`, with `moov` moved behind `mdat`
+        let others = [position(b"ftyp", 0, 20), position(b"mdat", 120, 50)];
+        let relocation = layout(&others, 2, 100);
+        assert_eq!(relocation.ranges, vec![(0, 20, 0), (120, 170, 20)]);
+        assert_eq!(relocation.map(120), 20);
+        assert_eq!(relocation.map(169), 69);
+        // offsets outside of all moved boxes are kept
+        assert_eq!(relocation.map(170), 170);
+
+        // a `moov` that grows by 10 bytes in front of `mdat`
+        let relocation = layout(&others, 1, 110);
+        assert_eq!(relocation.map(19), 19);
+        assert_eq!(relocation.map(128), 138);
+    }
+
+    #[test]
+    fn widens_offsets_moved_beyond_4_gib() {
+        // the first 100 bytes move to just below 4 GiB, the rest stays in place
+        let start = u64::from(u32::MAX) - 20;
+        let relocation = Relocation {
+            ranges: vec![(0, 100, start)],
+        };
+        let mut moov = moov(&[40, 1000]);
+        assert!(widen_overflowing(&mut moov, &relocation).unwrap());
+        assert_eq!(&stco(&moov).kind, b"co64");
+        assert_eq!(chunk_offsets(stco(&moov)).unwrap(), vec![40, 1000]);
+        assert!(!widen_overflowing(&mut moov, &relocation).unwrap());
+
+        relocate(&mut moov, &relocation).unwrap();
+        assert_eq!(chunk_offsets(stco(&moov)).unwrap(), vec![start + 40, 1000]);
+        assert_eq!(stco(&moov).data.len(), 8 + 2 * 8);
+    }
+
+    #[test]
+    fn keeps_offsets_below_4_gib_in_stco() {
+        let relocation = Relocation {
+            ranges: vec![(0, 100, u64::from(u32::MAX) - 100)],
+        };
+        let mut moov = moov(&[40, 99]);
+        assert!(!widen_overflowing(&mut moov, &relocation).unwrap());
+        relocate(&mut moov, &relocation).unwrap();
+        assert_eq!(&stco(&moov).kind, b"stco");
+        assert_eq!(
+            chunk_offsets(stco(&moov)).unwrap(),
+            vec![u64::from(u32::MAX) - 60, u64::from(u32::MAX) - 1]
+        );
+    }
+}
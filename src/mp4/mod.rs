@@ -0,0 +1,377 @@
+//! Native access to the iTunes metadata of MP4 files (`.mp4`, `.m4v`, `.m4a`)
+//!
+//! Unlike `SublerCli`, this works on every platform:
+//!
+//! ```rust,no_run
+//! use sublercli::mp4;
+//!
+//! let atoms = mp4::read_metadata("demo.mp4").unwrap();
+//! for atom in atoms.atoms() {
+//!     println!("{}: {}", atom.tag, atom.value);
+//! }
+//! let covers = mp4::read_artwork("demo.mp4").unwrap();
+//!
+//! let atoms = sublercli::Atoms::new().title("Foo Bar Title").build();
+//! mp4::write_metadata("demo.mp4", "demo.0.mp4", &atoms).unwrap();
+//!
+//! // move the `moov` box in front of the media data for progressive download
+//! mp4::optimize_file("demo.0.mp4", "demo.1.mp4").unwrap();
+//! ```
+
+use std::fs::File;
+use std::path::Path;
+
+use crate::cancel::Abort;
+use crate::mp4::layout::MoovPlacement;
+use crate::{Atoms, Result};
+
+mod boxes;
+pub(crate) mod ilst;
+mod layout;
+
+/// The path to the `ilst` box, starting at the children of `moov`
+const ILST_PATH: &[&boxes::FourCC] = &[b"udta", b"meta", b"ilst"];
+
+/// The encoding of an embedded image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Bmp,
+}
+
+impl ImageFormat {
+    /// Detects the format from the magic bytes at the start of the image
+    pub fn sniff(data: &[u8]) -> Option<ImageFormat> {
+        if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else {
+            None
+        }
+    }
+
+    /// The usual file extension of the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Bmp => "bmp",
+        }
+    }
+}
+
+/// A cover image stored in the `covr` item of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverArt {
+    /// The encoding of the image
+    pub format: ImageFormat,
+    /// The encoded image
+    pub data: Vec<u8>,
+}
+
+/// Reads the `ilst` items of the file
+fn read_items<P: AsRef<Path>>(path: P) -> Result<Vec<boxes::Mp4Box>> {
+    let mut file = File::open(path)?;
+    let (_, moov) = boxes::read_moov(&mut file)?;
+    Ok(moov
+        .find(ILST_PATH)
+        .and_then(|ilst| ilst.children.clone())
+        .unwrap_or_default())
+}
+
+/// Reads the existing metadata of the file into `Atoms`
+///
+/// Every known `ilst` item is mapped to its tag name in `Atoms::metadata_tags()`,
+/// unknown items are skipped. Cover art is available with `read_artwork`.
+///
+/// ```rust,no_run
+/// use sublercli::{mp4, AtomValue};
+///
+/// let atoms = mp4::read_metadata("demo.mp4").unwrap();
+/// // values are read as the kind of their tag
+/// if let Some(AtomValue::Integer(tempo)) = atoms.get("Tempo").map(|atom| &atom.value) {
+///     println!("{} bpm", tempo);
+/// }
+/// ```
+pub fn read_metadata<P: AsRef<Path>>(path: P) -> Result<Atoms> {
+    let mut builder = Atoms::new();
+    for item in read_items(path)? {
+        for atom in ilst::item_to_atoms(&item) {
+            builder.add_atom(atom);
+        }
+    }
+    Ok(builder.build())
+}
+
+/// Reads all cover images of the file
+pub fn read_artwork<P: AsRef<Path>>(path: P) -> Result<Vec<CoverArt>> {
+    Ok(read_items(path)?
+        .iter()
+        .flat_map(ilst::item_to_artwork)
+        .collect())
+}
+
+/// Writes `source` with the given metadata to `dest`
+///
+/// Existing items of every tag in `atoms` are replaced, all other existing metadata is kept,
+/// just like SublerCli does. Atoms created with `Atom::deletion` remove their tag.
+/// The chunk offsets of all tracks are fixed up if the size of the
+/// `moov` box changes. Fragmented files, with `moof` boxes, are not supported.
+///
+/// ```rust,no_run
+/// use sublercli::{mp4, Atoms};
+///
+/// let atoms = Atoms::new().title("Foo Bar Title").delete("Comments").build();
+/// mp4::write_metadata("demo.mp4", "demo.0.mp4", &atoms).unwrap();
+/// ```
+pub fn write_metadata<P: AsRef<Path>, Q: AsRef<Path>>(
+    source: P,
+    dest: Q,
+    atoms: &Atoms,
+) -> Result<()> {
+    write(
+        source.as_ref(),
+        dest.as_ref(),
+        Some(atoms),
+        false,
+        false,
+        &Abort::never(),
+    )
+}
+
+/// Writes `source` to `dest` with the `moov` box in front of the media data, like
+/// SublerCli's `-optimize`
+///
+/// All chunk offsets are rewritten, so the file can be played while it's still downloading.
+/// `stco` boxes are widened to `co64` if the moved media data ends up beyond 4 GiB.
+///
+/// ```rust,no_run
+/// use sublercli::mp4;
+///
+/// mp4::optimize_file("demo.mp4", "demo.0.mp4").unwrap();
+/// ```
+pub fn optimize_file<P: AsRef<Path>, Q: AsRef<Path>>(source: P, dest: Q) -> Result<()> {
+    write(
+        source.as_ref(),
+        dest.as_ref(),
+        None,
+        false,
+        true,
+        &Abort::never(),
+    )
+}
+
+/// Writes `source` to `dest`, optionally with new metadata and optimized
+///
+/// With `remove_existing` all existing metadata items are dropped before `atoms` are written.
+/// The copy of the media data stops with an error as soon as `abort` fires.
+pub(crate) fn write(
+    source: &Path,
+    dest: &Path,
+    atoms: Option<&Atoms>,
+    remove_existing: bool,
+    optimize: bool,
+    abort: &Abort,
+) -> Result<()> {
+    let (_, mut moov) = boxes::read_moov(&mut File::open(source)?)?;
+    if let Some(atoms) = atoms {
+        let meta = moov
+            .child_or_insert(b"udta", || {
+                boxes::Mp4Box::container(b"udta", Vec::new(), Vec::new())
+            })
+            .child_or_insert(b"meta", || {
+                boxes::Mp4Box::container(b"meta", vec![0; 4], vec![metadata_handler()])
+            });
+        let existing = if remove_existing {
+            None
+        } else {
+            meta.child(b"ilst")
+        };
+        let ilst = ilst::build_ilst(existing, atoms)?;
+        match meta.child_mut(b"ilst") {
+            Some(existing) => *existing = ilst,
+            None => meta.children.get_or_insert_with(Vec::new).push(ilst),
+        }
+    }
+    let placement = if optimize {
+        MoovPlacement::BeforeMdat
+    } else {
+        MoovPlacement::Keep
+    };
+    layout::rewrite(source, dest, moov, placement, abort)
+}
+
+/// The `hdlr` box that marks a `meta` box as iTunes metadata
+fn metadata_handler() -> boxes::Mp4Box {
+    let mut data = vec![0; 8];
+    data.extend_from_slice(b"mdirappl");
+    data.extend_from_slice(&[0; 9]);
+    boxes::Mp4Box::leaf(b"hdlr", data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_util::{self, bx};
+    use crate::{Artwork, AtomValue, ReleaseDate, SublerError};
+
+    /// The tags of the metadata of the file, sorted
+    fn tags(path: &Path) -> Vec<String> {
+        let mut tags: Vec<_> = read_metadata(path)
+            .unwrap()
+            .atoms()
+            .iter()
+            .map(|atom| atom.tag.clone())
+            .collect();
+        tags.sort();
+        tags
+    }
+
+    /// The tags and values of the atoms, sorted by tag
+    fn values(atoms: &Atoms) -> Vec<(String, AtomValue)> {
+        let mut values: Vec<_> = atoms
+            .atoms()
+            .iter()
+            .map(|atom| (atom.tag.clone(), atom.value.clone()))
+            .collect();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        values
+    }
+
+    #[test]
+    fn reads_back_written_values_as_their_kind() {
+        let source = test_util::mp4_file("mp4-round-trip");
+        let dest = source.with_file_name("dest.mp4");
+        assert!(read_metadata(&source).unwrap().atoms().is_empty());
+
+        let atoms = Atoms::new()
+            .title("Foo Bar Title")
+            .artist("Foo Artist")
+            .comments("first line\nsecond line")
+            .track_number(3, Some(12))
+            .tempo(120)
+            .hd_video(true)
+            .release_date(ReleaseDate::from_ymd(2018, 4, 1).unwrap())
+            .build();
+        write_metadata(&source, &dest, &atoms).unwrap();
+        assert_eq!(values(&read_metadata(&dest).unwrap()), values(&atoms));
+    }
+
+    #[test]
+    fn relocates_chunks_when_moov_grows() {
+        // `moov` in front of `mdat`, so the media data moves when `moov` grows
+        let ftyp = test_util::ftyp();
+        let moov_len = test_util::moov(0).len();
+        let offset = (ftyp.len() + moov_len + 8) as u32;
+        let original = [ftyp, test_util::moov(offset), bx(b"mdat", b"SAMPLE")].concat();
+        let source = test_util::test_dir("mp4-grow").join("demo.mp4");
+        let dest = source.with_file_name("dest.mp4");
+        fs::write(&source, &original).unwrap();
+        assert_eq!(test_util::first_chunk(&original), b"SAMPLE");
+
+        let atoms = Atoms::new()
+            .title("A title long enough to grow the moov box")
+            .build();
+        write_metadata(&source, &dest, &atoms).unwrap();
+        let written = fs::read(&dest).unwrap();
+        assert!(written.len() > original.len());
+        assert_eq!(test_util::first_chunk(&written), b"SAMPLE");
+    }
+
+    #[test]
+    fn rejects_numbers_that_dont_fit_their_item() {
+        let source = test_util::mp4_file("mp4-tempo");
+        let dest = source.with_file_name("dest.mp4");
+        // `Tempo` has 16 bits
+        match write_metadata(&source, &dest, &Atoms::new().tempo(70000).build()) {
+            Err(SublerError::InvalidAtomValue { tag, .. }) => assert_eq!(tag, "Tempo"),
+            other => panic!("{:?}", other),
+        }
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn rejects_fragmented_files() {
+        let source = test_util::test_dir("mp4-fragmented").join("demo.mp4");
+        let dest = source.with_file_name("dest.mp4");
+        let moov = bx(b"moov", &bx(b"mvex", &bx(b"trex", &[0; 24])));
+        fs::write(&source, [test_util::ftyp(), moov].concat()).unwrap();
+        match write_metadata(&source, &dest, &Atoms::new().title("Foo").build()) {
+            Err(SublerError::Unsupported(_)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn moves_moov_in_front_of_mdat() {
+        let source = test_util::mp4_file("mp4-optimize");
+        let dest = source.with_file_name("dest.mp4");
+        let original = fs::read(&source).unwrap();
+        optimize_file(&source, &dest).unwrap();
+        let optimized = fs::read(&dest).unwrap();
+        let ftyp_len = test_util::ftyp().len();
+        assert_eq!(&optimized[ftyp_len + 4..ftyp_len + 8], b"moov");
+        assert_eq!(optimized.len(), original.len());
+        assert_eq!(test_util::first_chunk(&optimized), b"SAMPLE");
+    }
+
+    #[test]
+    fn reads_covers_in_their_format() {
+        let source = test_util::mp4_file("mp4-covers");
+        let dest = source.with_file_name("dest.mp4");
+        let png = b"\x89PNG\r\n\x1a\n....".to_vec();
+        let jpeg = vec![0xff, 0xd8, 0xff, 0xe0];
+        let atoms = Atoms::new()
+            .artwork(Artwork::from_bytes(png.clone()))
+            .add_artwork(Artwork::from_bytes(jpeg.clone()))
+            .build();
+        write_metadata(&source, &dest, &atoms).unwrap();
+        let covers = read_artwork(&dest).unwrap();
+        let covers: Vec<_> = covers.iter().map(|c| (c.format, c.data.clone())).collect();
+        assert_eq!(
+            covers,
+            vec![(ImageFormat::Png, png), (ImageFormat::Jpeg, jpeg)]
+        );
+    }
+
+    #[test]
+    fn deletes_existing_items() {
+        let source = test_util::mp4_file("mp4-delete");
+        let (tagged, dest) = (
+            source.with_file_name("tagged.mp4"),
+            source.with_file_name("dest.mp4"),
+        );
+        let atoms = Atoms::new().title("Foo").artist("Bar").build();
+        write_metadata(&source, &tagged, &atoms).unwrap();
+        assert_eq!(tags(&tagged), vec!["Artist", "Name"]);
+
+        write_metadata(&tagged, &dest, &Atoms::new().delete("Name").build()).unwrap();
+        assert_eq!(tags(&dest), vec!["Artist"]);
+        // deleting a tag the file doesn't have is no error
+        write_metadata(&dest, &tagged, &Atoms::new().delete("Genre").build()).unwrap();
+        assert_eq!(tags(&tagged), vec!["Artist"]);
+    }
+
+    #[test]
+    fn removes_existing_items() {
+        let source = test_util::mp4_file("mp4-remove-existing");
+        let (tagged, dest) = (
+            source.with_file_name("tagged.mp4"),
+            source.with_file_name("dest.mp4"),
+        );
+        let atoms = Atoms::new().title("Foo").artist("Bar").build();
+        write_metadata(&source, &tagged, &atoms).unwrap();
+
+        let genre = Atoms::new().genre("Drama").build();
+        write(&tagged, &dest, Some(&genre), true, false, &Abort::never()).unwrap();
+        assert_eq!(tags(&dest), vec!["Genre"]);
+        write(&tagged, &dest, Some(&genre), false, false, &Abort::never()).unwrap();
+        assert_eq!(tags(&dest), vec!["Artist", "Genre", "Name"]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Shrinking covers before they are embedded, enabled with the `image` feature
+
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageReader, Rgb, RgbImage};
+
+use crate::artwork::atom_artwork;
+use crate::{Artwork, AtomValue, Atoms, Result, SublerError};
+
+/// How covers are normalized, see `Artwork::normalize`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArtworkOptions {
+    /// The longest side of the cover in pixels, larger covers are scaled down
+    pub max_size: u32,
+    /// The JPEG quality from 1 to 100
+    pub quality: u8,
+}
+
+impl Default for ArtworkOptions {
+    fn default() -> Self {
+        ArtworkOptions {
+            max_size: 1400,
+            quality: 90,
+        }
+    }
+}
+
+impl Artwork {
+    /// The cover as an in-memory JPEG, scaled down to `options.max_size` and without the
+    /// EXIF and other metadata of the original
+    ///
+    /// The EXIF orientation is applied before it's dropped, so rotated photos stay upright.
+    /// Transparent areas are filled with white. Fails with `SublerError::InvalidArtwork`
+    /// if the image can't be decoded.
+    ///
+    /// ```rust
+    /// use sublercli::{mp4::ImageFormat, Artwork, ArtworkOptions};
+    /// # let mut png = Vec::new();
+    /// # image::DynamicImage::new_rgb8(3000, 2000)
+    /// #     .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+    /// #     .unwrap();
+    /// let cover = Artwork::from_bytes(png).normalize(&ArtworkOptions::default()).unwrap();
+    /// assert_eq!(cover.format().unwrap(), ImageFormat::Jpeg);
+    /// let cover = image::load_from_memory(cover.bytes().unwrap()).unwrap();
+    /// assert_eq!((cover.width(), cover.height()), (1400, 933));
+    ///
+    /// // a landscape JPEG tagged to be rotated by 90 degrees ends up in portrait
+    /// # let mut jpeg = Vec::new();
+    /// # image::DynamicImage::new_rgb8(300, 200)
+    /// #     .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+    /// #     .unwrap();
+    /// # let exif = b"Exif\0\0MM\0*\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0";
+    /// # let mut app1 = vec![0xff, 0xe1];
+    /// # app1.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+    /// # app1.extend_from_slice(exif);
+    /// # jpeg.splice(2..2, app1);
+    /// let cover = Artwork::from_bytes(jpeg).normalize(&ArtworkOptions::default()).unwrap();
+    /// let cover = image::load_from_memory(cover.bytes().unwrap()).unwrap();
+    /// assert_eq!((cover.width(), cover.height()), (200, 300));
+    /// ```
+    pub fn normalize(&self, options: &ArtworkOptions) -> Result<Artwork> {
+        let invalid =
+            |err: image::ImageError| SublerError::InvalidArtwork(format!("{}: {}", self, err));
+        let data = self.load()?.data;
+        let mut decoder = ImageReader::new(Cursor::new(&data))
+            .with_guessed_format()
+            .map_err(|err| invalid(err.into()))?
+            .into_decoder()
+            .map_err(invalid)?;
+        let orientation = decoder.orientation().map_err(invalid)?;
+        let mut image = DynamicImage::from_decoder(decoder).map_err(invalid)?;
+        image.apply_orientation(orientation);
+        let (width, height) = image.dimensions();
+        if width.max(height) > options.max_size {
+            image = image.resize(options.max_size, options.max_size, FilterType::Lanczos3);
+        }
+        let rgb = if image.color().has_alpha() {
+            flatten(&image)
+        } else {
+            image.to_rgb8()
+        };
+        let mut data = Vec::new();
+        let encoder = JpegEncoder::new_with_quality(&mut data, options.quality.clamp(1, 100));
+        DynamicImage::ImageRgb8(rgb)
+            .write_with_encoder(encoder)
+            .map_err(invalid)?;
+        Ok(Artwork::from_bytes(data))
+    }
+}
+
+/// The image drawn on a white background
+fn flatten(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let pixel = rgba.get_pixel(x, y);
+        let alpha = u32::from(pixel[3]);
+        let blend = |channel: u8| ((u32::from(channel) * alpha + 255 * (255 - alpha)) / 255) as u8;
+        Rgb([blend(pixel[0]), blend(pixel[1]), blend(pixel[2])])
+    })
+}
+
+impl Atoms {
+    /// Replaces every cover with its normalized form, see `Artwork::normalize`
+    pub fn normalize_artwork(&mut self, options: &ArtworkOptions) -> Result<&mut Self> {
+        for atom in self.atoms_mut() {
+            if let Some(artwork) = atom_artwork(atom) {
+                atom.value = AtomValue::Image(artwork.normalize(options)?);
+            }
+        }
+        Ok(self)
+    }
+}
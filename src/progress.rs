@@ -0,0 +1,188 @@
+//! Progress reports parsed from the output of SublerCli while it runs
+
+use std::fmt;
+use std::sync::Arc;
+
+/// The step of a tagging run that is currently executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The source is muxed into the destination and the metadata is written
+    Muxing,
+    /// The destination is rewritten with the `moov` box in front, SublerCli's `-optimize`
+    Optimizing,
+    /// The subtitle tracks are added to the destination
+    Subtitles,
+}
+
+/// A progress report of a running tagging run
+///
+/// A report without a `percent` announces the start of a new `phase`.
+///
+/// ```rust
+/// use sublercli::{Phase, Progress};
+/// let progress = Progress::parse(Phase::Muxing, "Optimizing: 42.5%").unwrap();
+/// assert_eq!(progress.phase, Phase::Optimizing);
+/// assert_eq!(progress.percent, Some(42.5));
+///
+/// assert_eq!(Progress::parse(Phase::Muxing, "Warning: something odd"), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The current phase
+    pub phase: Phase,
+    /// How much of the phase is done, from 0 to 100
+    pub percent: Option<f32>,
+}
+
+impl Progress {
+    /// Parses a line of SublerCli output printed during `phase`
+    ///
+    /// Lines mentioning optimizing or muxing switch the phase, the last `N%` in a line is
+    /// taken as the percentage. Returns `None` for lines that carry neither.
+    pub fn parse(phase: Phase, line: &str) -> Option<Progress> {
+        let lower = line.to_lowercase();
+        let new_phase = if lower.contains("optimiz") {
+            Some(Phase::Optimizing)
+        } else if lower.contains("mux") {
+            Some(Phase::Muxing)
+        } else {
+            None
+        };
+        let percent = parse_percent(line);
+        if new_phase.is_none() && percent.is_none() {
+            return None;
+        }
+        Some(Progress {
+            phase: new_phase.unwrap_or(phase),
+            percent,
+        })
+    }
+}
+
+/// The number in front of the last `%` of the line
+fn parse_percent(line: &str) -> Option<f32> {
+    let before = &line[..line.rfind('%')?];
+    let start = before
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !(c.is_ascii_digit() || *c == '.'))
+        .map_or(0, |(pos, c)| pos + c.len_utf8());
+    let percent: f32 = before[start..].parse().ok()?;
+    Some(percent.clamp(0.0, 100.0))
+}
+
+/// A function that receives the progress of a tagging run, set with `Subler::progress`
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(Progress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new<F: Fn(Progress) + Send + Sync + 'static>(callback: F) -> Self {
+        ProgressCallback(Arc::new(callback))
+    }
+
+    /// Reports the progress to the callback
+    pub fn call(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Turns the streamed output of SublerCli into calls of a `ProgressCallback`
+#[derive(Debug)]
+pub(crate) struct ProgressParser {
+    callback: ProgressCallback,
+    phase: Phase,
+    /// The output after the last line break
+    pending: Vec<u8>,
+    last: Option<Progress>,
+}
+
+impl ProgressParser {
+    pub fn new(callback: ProgressCallback) -> Self {
+        ProgressParser {
+            callback,
+            phase: Phase::Muxing,
+            pending: Vec::new(),
+            last: None,
+        }
+    }
+
+    /// Announces the start of `phase`
+    pub fn enter(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.emit(Progress {
+            phase,
+            percent: None,
+        });
+    }
+
+    /// Parses the next chunk of output, progress bars redrawn with `\r` are understood
+    pub fn feed(&mut self, output: &[u8]) {
+        self.pending.extend_from_slice(output);
+        while let Some(pos) = self.pending.iter().position(|b| *b == b'\r' || *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            self.line(&String::from_utf8_lossy(&line[..pos]));
+        }
+    }
+
+    /// Parses the output after the last line break, once the process finished
+    pub fn finish(&mut self) {
+        let line = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        self.line(&line);
+    }
+
+    fn line(&mut self, line: &str) {
+        if let Some(progress) = Progress::parse(self.phase, line) {
+            if progress.phase != self.phase {
+                self.enter(progress.phase);
+            }
+            if progress.percent.is_some() {
+                self.emit(progress);
+            }
+        }
+    }
+
+    /// Calls the callback, unless the progress didn't change
+    fn emit(&mut self, progress: Progress) {
+        if self.last != Some(progress) {
+            self.last = Some(progress);
+            self.callback.call(progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_percent_after_non_ascii_text() {
+        let progress = Progress::parse(Phase::Muxing, "Muxing…42%").unwrap();
+        assert_eq!(progress.percent, Some(42.0));
+        let progress = Progress::parse(Phase::Muxing, "Écriture 7.5%").unwrap();
+        assert_eq!(progress.percent, Some(7.5));
+        assert_eq!(
+            Progress::parse(Phase::Muxing, "Muxing…%").unwrap().percent,
+            None
+        );
+    }
+
+    #[test]
+    fn feeds_redrawn_progress_bars() {
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let mut parser = ProgressParser::new(ProgressCallback::new(move |progress: Progress| {
+            sink.lock().unwrap().push(progress.percent)
+        }));
+        parser.feed("Optimizing…10%\rOptimizing…".as_bytes());
+        parser.feed(b"55%\r");
+        parser.finish();
+        assert_eq!(*seen.lock().unwrap(), vec![None, Some(10.0), Some(55.0)]);
+    }
+}
@@ -0,0 +1,230 @@
+//! Content ratings in the form iTunes stores them in the `Rating` atom
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::SublerError;
+
+/// A rating system, the first field of an iTunes rating string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RatingSystem {
+    /// US movies, MPAA
+    Mpaa,
+    /// US television, TV Parental Guidelines
+    UsTv,
+    /// UK movies, BBFC
+    UkMovie,
+    /// German movies, FSK
+    DeMovie,
+    /// German television, FSK
+    DeTv,
+    /// Australian movies, ACB
+    AuMovie,
+    /// Australian television
+    AuTv,
+}
+
+impl RatingSystem {
+    /// All supported systems
+    pub fn all() -> &'static [RatingSystem] {
+        &[
+            RatingSystem::Mpaa,
+            RatingSystem::UsTv,
+            RatingSystem::UkMovie,
+            RatingSystem::DeMovie,
+            RatingSystem::DeTv,
+            RatingSystem::AuMovie,
+            RatingSystem::AuTv,
+        ]
+    }
+
+    /// The name of the system in iTunes rating strings
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RatingSystem::Mpaa => "mpaa",
+            RatingSystem::UsTv => "us-tv",
+            RatingSystem::UkMovie => "uk-movie",
+            RatingSystem::DeMovie => "de-movie",
+            RatingSystem::DeTv => "de-tv",
+            RatingSystem::AuMovie => "au-movie",
+            RatingSystem::AuTv => "au-tv",
+        }
+    }
+}
+
+impl FromStr for RatingSystem {
+    type Err = SublerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        RatingSystem::all()
+            .iter()
+            .find(|system| system.as_str().eq_ignore_ascii_case(s))
+            .cloned()
+            .ok_or_else(|| SublerError::UnknownRating(s.to_owned()))
+    }
+}
+
+/// All known ratings: system, label and the numeric code iTunes sorts and filters by
+const RATINGS: &[(RatingSystem, &str, u16)] = &[
+    (RatingSystem::Mpaa, "G", 100),
+    (RatingSystem::Mpaa, "PG", 200),
+    (RatingSystem::Mpaa, "PG-13", 300),
+    (RatingSystem::Mpaa, "R", 400),
+    (RatingSystem::Mpaa, "NC-17", 500),
+    (RatingSystem::UsTv, "TV-Y", 100),
+    (RatingSystem::UsTv, "TV-Y7", 200),
+    (RatingSystem::UsTv, "TV-G", 300),
+    (RatingSystem::UsTv, "TV-PG", 400),
+    (RatingSystem::UsTv, "TV-14", 500),
+    (RatingSystem::UsTv, "TV-MA", 600),
+    (RatingSystem::UkMovie, "U", 100),
+    (RatingSystem::UkMovie, "Uc", 150),
+    (RatingSystem::UkMovie, "PG", 200),
+    (RatingSystem::UkMovie, "12", 300),
+    (RatingSystem::UkMovie, "12A", 325),
+    (RatingSystem::UkMovie, "15", 350),
+    (RatingSystem::UkMovie, "18", 400),
+    (RatingSystem::UkMovie, "R18", 600),
+    (RatingSystem::DeMovie, "Ab 0 Jahren", 75),
+    (RatingSystem::DeMovie, "Ab 6 Jahren", 100),
+    (RatingSystem::DeMovie, "Ab 12 Jahren", 200),
+    (RatingSystem::DeMovie, "Ab 16 Jahren", 500),
+    (RatingSystem::DeMovie, "Ab 18 Jahren", 600),
+    (RatingSystem::DeTv, "Ab 0 Jahren", 75),
+    (RatingSystem::DeTv, "Ab 6 Jahren", 100),
+    (RatingSystem::DeTv, "Ab 12 Jahren", 200),
+    (RatingSystem::DeTv, "Ab 16 Jahren", 500),
+    (RatingSystem::DeTv, "Ab 18 Jahren", 600),
+    (RatingSystem::AuMovie, "G", 100),
+    (RatingSystem::AuMovie, "PG", 200),
+    (RatingSystem::AuMovie, "M", 350),
+    (RatingSystem::AuMovie, "MA 15+", 375),
+    (RatingSystem::AuMovie, "R18+", 400),
+    (RatingSystem::AuTv, "P", 100),
+    (RatingSystem::AuTv, "C", 200),
+    (RatingSystem::AuTv, "G", 300),
+    (RatingSystem::AuTv, "PG", 400),
+    (RatingSystem::AuTv, "M", 500),
+    (RatingSystem::AuTv, "MA 15+", 550),
+    (RatingSystem::AuTv, "AV 15+", 575),
+    (RatingSystem::AuTv, "R18+", 600),
+];
+
+/// The label iTunes uses for unrated content, in every system
+const UNRATED: &str = "Unrated";
+
+/// The code iTunes stores for unrated content instead of a number
+const UNRATED_CODE: &str = "???";
+
+/// A content rating of a known rating system
+///
+/// Renders as the iTunes rating string `system|label|code|` and parses it back. The code is
+/// optional when parsing and filled in from the label, FSK labels can also be given as
+/// `FSK 12`. Every system has an `Unrated` rating, with `???` as its code:
+///
+/// ```rust
+/// use sublercli::{ContentRating, RatingSystem};
+/// let rating = ContentRating::new(RatingSystem::Mpaa, "PG-13").unwrap();
+/// assert_eq!(rating.to_string(), "mpaa|PG-13|300|");
+/// assert_eq!(rating.code(), Some(300));
+///
+/// let rating: ContentRating = "de-movie|FSK 12".parse().unwrap();
+/// assert_eq!(rating.to_string(), "de-movie|Ab 12 Jahren|200|");
+///
+/// let rating: ContentRating = "uk-movie|12A|325|".parse().unwrap();
+/// assert_eq!(rating.label(), "12A");
+///
+/// // a code that doesn't belong to the label is rejected
+/// assert!("uk-movie|15|300|".parse::<ContentRating>().is_err());
+/// assert!("mpaa|PG-14|".parse::<ContentRating>().is_err());
+///
+/// let unrated: ContentRating = "mpaa|Unrated|???|".parse().unwrap();
+/// assert_eq!(unrated.code(), None);
+/// assert_eq!(unrated.to_string(), "mpaa|Unrated|???|");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentRating {
+    system: RatingSystem,
+    label: &'static str,
+    code: Option<u16>,
+}
+
+impl ContentRating {
+    /// The rating with the label in the system, the label is matched ignoring case
+    pub fn new(system: RatingSystem, label: &str) -> crate::Result<ContentRating> {
+        let label = label.trim();
+        let fsk_age = match system {
+            RatingSystem::DeMovie | RatingSystem::DeTv => label
+                .get(..4)
+                .filter(|prefix| prefix.eq_ignore_ascii_case("FSK "))
+                .map(|_| label[4..].trim()),
+            _ => None,
+        };
+        ContentRating::all()
+            .find(|rating| {
+                rating.system == system
+                    && (rating.label.eq_ignore_ascii_case(label)
+                        || fsk_age.is_some_and(|age| rating.label == format!("Ab {} Jahren", age)))
+            })
+            .ok_or_else(|| SublerError::UnknownRating(format!("{}|{}", system.as_str(), label)))
+    }
+
+    /// All known ratings, the `Unrated` ones last
+    pub fn all() -> impl Iterator<Item = ContentRating> {
+        let rated = RATINGS.iter().map(|&(system, label, code)| ContentRating {
+            system,
+            label,
+            code: Some(code),
+        });
+        let unrated = RatingSystem::all().iter().map(|&system| ContentRating {
+            system,
+            label: UNRATED,
+            code: None,
+        });
+        rated.chain(unrated)
+    }
+
+    pub fn system(&self) -> RatingSystem {
+        self.system
+    }
+
+    /// The label as shown to the user, like `PG-13`
+    pub fn label(&self) -> &'static str {
+        self.label
+    }
+
+    /// The numeric code iTunes uses to compare ratings, `None` for `Unrated`
+    pub fn code(&self) -> Option<u16> {
+        self.code
+    }
+}
+
+impl fmt::Display for ContentRating {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = self
+            .code
+            .map_or_else(|| UNRATED_CODE.to_owned(), |code| code.to_string());
+        write!(f, "{}|{}|{}|", self.system.as_str(), self.label, code)
+    }
+}
+
+impl FromStr for ContentRating {
+    type Err = SublerError;
+
+    /// Parses `system|label|code|`, the code and trailing fields are optional
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || SublerError::UnknownRating(s.to_owned());
+        let mut fields = s.split('|');
+        let system: RatingSystem = fields.next().unwrap_or_default().parse()?;
+        let label = fields.next().ok_or_else(unknown)?;
+        let rating = ContentRating::new(system, label).map_err(|_| unknown())?;
+        match fields.next().map(str::trim).filter(|code| !code.is_empty()) {
+            Some(code) if rating.code.is_none() && code != UNRATED_CODE => Err(unknown()),
+            Some(code) if rating.code.is_some() && code.parse::<u16>().ok() != rating.code => {
+                Err(unknown())
+            }
+            _ => Ok(rating),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Rendering commands as POSIX shell command lines
+
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// Quotes a single argument so a POSIX shell passes it on unchanged
+///
+/// Arguments made of safe characters only are kept as they are, everything else is
+/// wrapped in single quotes.
+pub(crate) fn quote(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy();
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%^".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        return arg.into_owned();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// The program and all arguments of the command, quoted and joined by spaces
+pub(crate) fn command_line(cmd: &Command) -> String {
+    let mut line = quote(cmd.get_program());
+    for arg in cmd.get_args() {
+        line.push(' ');
+        line.push_str(&quote(arg));
+    }
+    line
+}
@@ -0,0 +1,78 @@
+//! Subtitle files that are muxed into the tagged file
+
+use std::path::PathBuf;
+
+use crate::Language;
+
+/// A subtitle file, like an `.srt`, that is added as a subtitle track
+///
+/// ```rust
+/// use sublercli::{Language, Subtitle};
+/// let english: Language = "en".parse().unwrap();
+/// let subtitle = Subtitle::new("movie.en.srt")
+///     .language(english)
+///     .delay(-250)
+///     .height(60);
+/// assert_eq!(
+///     subtitle.args(),
+///     vec!["-language", "English", "-delay", "-250", "-height", "60"]
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subtitle {
+    /// The path to the subtitle file
+    pub path: PathBuf,
+    /// The language of the track, SublerCli's `-language`
+    pub language: Option<Language>,
+    /// The delay of the subtitles in milliseconds, SublerCli's `-delay`
+    pub delay: Option<i64>,
+    /// The height of the track in pixels, SublerCli's `-height`
+    pub height: Option<u32>,
+}
+
+impl Subtitle {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Subtitle {
+            path: path.into(),
+            language: None,
+            delay: None,
+            height: None,
+        }
+    }
+
+    /// sets the language of the track
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    /// sets the delay in milliseconds, negative values show the subtitles earlier
+    pub fn delay(mut self, millis: i64) -> Self {
+        self.delay = Some(millis);
+        self
+    }
+
+    /// sets the height of the track in pixels
+    pub fn height(mut self, height: u32) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    /// The SublerCli options for this track
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(language) = self.language {
+            args.push("-language".to_owned());
+            args.push(language.to_string());
+        }
+        if let Some(delay) = self.delay {
+            args.push("-delay".to_owned());
+            args.push(delay.to_string());
+        }
+        if let Some(height) = self.height {
+            args.push("-height".to_owned());
+            args.push(height.to_string());
+        }
+        args
+    }
+}
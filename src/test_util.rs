@@ -0,0 +1,76 @@
+//! Fixtures shared by the unit tests
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// A fresh, empty directory for the test `name`
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("sublercli-test-{}-{}", process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// An empty `demo.mp4` in the directory of the test `name`, for tests that never read it
+pub(crate) fn source_file(name: &str) -> PathBuf {
+    let file = test_dir(name).join("demo.mp4");
+    fs::write(&file, b"").unwrap();
+    file
+}
+
+/// A box of type `kind` with the given payload
+pub(crate) fn bx(kind: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut b = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+    b.extend_from_slice(kind);
+    b.extend_from_slice(payload);
+    b
+}
+
+/// The `ftyp` box of the test files
+pub(crate) fn ftyp() -> Vec<u8> {
+    bx(b"ftyp", b"isom\0\0\0\0isom")
+}
+
+/// A `moov` with a single track holding one chunk at `offset`, in a 32 bit `stco`
+pub(crate) fn moov(offset: u32) -> Vec<u8> {
+    let mut entries = vec![0, 0, 0, 0, 0, 0, 0, 1];
+    entries.extend_from_slice(&offset.to_be_bytes());
+    let stbl = bx(b"stbl", &bx(b"stco", &entries));
+    bx(b"moov", &bx(b"trak", &bx(b"mdia", &bx(b"minf", &stbl))))
+}
+
+/// The bytes of the `SAMPLE` the first chunk offset of the file points to
+pub(crate) fn first_chunk(file: &[u8]) -> &[u8] {
+    let stco = file.windows(4).position(|w| w == b"stco").unwrap();
+    let offset = u32::from_be_bytes([
+        file[stco + 12],
+        file[stco + 13],
+        file[stco + 14],
+        file[stco + 15],
+    ]) as usize;
+    &file[offset..offset + 6]
+}
+
+/// A minimal `demo.mp4` in the directory of the test `name`: `ftyp`, the `mdat` with a
+/// single `SAMPLE` and the `moov` at the end
+pub(crate) fn mp4_file(name: &str) -> PathBuf {
+    let file = test_dir(name).join("demo.mp4");
+    let ftyp = ftyp();
+    let offset = ftyp.len() as u32 + 8;
+    fs::write(&file, [ftyp, bx(b"mdat", b"SAMPLE"), moov(offset)].concat()).unwrap();
+    file
+}
+
+/// A SublerCli stand-in in `dir` that runs the shell `script`, `$dest` is its `-dest`
+#[cfg(unix)]
+pub(crate) fn fake_cli(dir: &Path, script: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = dir.join("SublerCli");
+    let head = "#!/bin/sh\nwhile [ $# -gt 0 ]; do [ \"$1\" = -dest ] && dest=$2; shift; done\n";
+    fs::write(&file, format!("{}{}\n", head, script)).unwrap();
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o755)).unwrap();
+    file
+}
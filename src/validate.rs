@@ -0,0 +1,192 @@
+//! Checking `Atoms` against the declared tags before tagging
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::mp4::ilst;
+use crate::value::{parse_flag, parse_pair};
+use crate::{
+    Artwork, Atom, AtomKind, AtomValue, Atoms, ContentRating, RatingSystem, ReleaseDate, Result,
+    SublerError,
+};
+
+/// A problem with a single atom, found by `Atoms::validate`
+#[derive(Debug, Clone, PartialEq)]
+pub enum AtomProblem {
+    /// The tag is not one of `Atoms::metadata_tags()`
+    UnknownTag(String),
+    /// The value doesn't match the `AtomKind` of the tag
+    InvalidValue {
+        /// The tag of the atom
+        tag: String,
+        /// The rejected value
+        value: String,
+        /// The kind of value the tag holds
+        expected: AtomKind,
+    },
+    /// The rating is not in the iTunes `system|label|code|` form, like `mpaa|PG-13|300|`,
+    /// or not a known `ContentRating` of its system
+    InvalidRating(String),
+    /// The artwork file doesn't exist
+    MissingArtwork(PathBuf),
+    /// The artwork is not a JPEG, PNG or BMP image
+    UnsupportedArtwork(String),
+}
+
+impl fmt::Display for AtomProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtomProblem::UnknownTag(tag) => write!(f, "Unknown tag: {}", tag),
+            AtomProblem::InvalidValue {
+                tag,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value for {}: {:?}, expected {:?}",
+                tag, value, expected
+            ),
+            AtomProblem::InvalidRating(rating) => write!(
+                f,
+                "Invalid rating: {:?}, expected system|label|code|",
+                rating
+            ),
+            AtomProblem::MissingArtwork(path) => {
+                write!(f, "Artwork file does not exist: {}", path.display())
+            }
+            AtomProblem::UnsupportedArtwork(artwork) => {
+                write!(f, "Artwork is not a JPEG, PNG or BMP image: {}", artwork)
+            }
+        }
+    }
+}
+
+impl Atoms {
+    /// Checks every atom against its declared tag and returns all problems at once
+    ///
+    /// Fails with `SublerError::InvalidAtoms` on unknown tags, values that don't match
+    /// the `AtomKind` of their tag or don't fit its item, malformed ratings and missing or
+    /// unsupported artwork.
+    /// Deletions are only checked for their tag.
+    ///
+    /// ```rust
+    /// use sublercli::*;
+    /// let atoms = Atoms::new()
+    ///     .title("Foo")
+    ///     .track_number(3, Some(12))
+    ///     .add("TV Season", "two")
+    ///     .add("Disk #", "1/x")
+    ///     .add("Colour", "red")
+    ///     .rating("PG-13")
+    ///     .artwork("missing-cover.jpg")
+    ///     .build();
+    /// match atoms.validate() {
+    ///     Err(SublerError::InvalidAtoms(problems)) => {
+    ///         assert_eq!(problems.len(), 5);
+    ///         assert_eq!(problems[2], AtomProblem::UnknownTag("Colour".to_owned()));
+    ///     }
+    ///     other => panic!("{:?}", other),
+    /// }
+    /// assert!(Atoms::new().title("Foo").tv_season(2).build().validate().is_ok());
+    /// assert!(Atoms::new().rating("mpaa|Unrated|???|").build().validate().is_ok());
+    /// // `Tempo` is stored in 16 bits
+    /// assert!(Atoms::new().tempo(70000).build().validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        let problems: Vec<AtomProblem> = self.atoms().iter().filter_map(check).collect();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(SublerError::InvalidAtoms(problems))
+        }
+    }
+}
+
+/// The problem of a single atom, if any
+fn check(atom: &Atom) -> Option<AtomProblem> {
+    let kind = match Atoms::tag_kind(&atom.tag) {
+        Some(kind) => kind,
+        None => return Some(AtomProblem::UnknownTag(atom.tag.clone())),
+    };
+    if atom.is_deletion() {
+        return None;
+    }
+    let invalid = || AtomProblem::InvalidValue {
+        tag: atom.tag.clone(),
+        value: atom.value.to_string(),
+        expected: kind,
+    };
+    if atom.tag == "Rating" {
+        return rating_problem(&atom.value.to_string());
+    }
+    match (&atom.value, kind) {
+        (AtomValue::Image(artwork), AtomKind::Image) => artwork_problem(artwork),
+        (AtomValue::Pair { number, total }, AtomKind::Pair) => {
+            if is_valid_pair(*number, *total) {
+                None
+            } else {
+                Some(invalid())
+            }
+        }
+        (AtomValue::Text(text), _) => match kind {
+            AtomKind::Text => None,
+            AtomKind::Date if text.parse::<ReleaseDate>().is_err() => Some(invalid()),
+            AtomKind::Image => artwork_problem(&Artwork::from_path(text.as_str())),
+            AtomKind::Integer
+                if text
+                    .trim()
+                    .parse::<i64>()
+                    .map_or(true, |number| !ilst::integer_fits_tag(&atom.tag, number)) =>
+            {
+                Some(invalid())
+            }
+            AtomKind::Boolean if parse_flag(text).is_none() => Some(invalid()),
+            AtomKind::Pair
+                if parse_pair(text).is_none_or(|(number, total)| !is_valid_pair(number, total)) =>
+            {
+                Some(invalid())
+            }
+            _ => None,
+        },
+        (AtomValue::Integer(number), AtomKind::Integer) => {
+            if ilst::integer_fits_tag(&atom.tag, *number) {
+                None
+            } else {
+                Some(invalid())
+            }
+        }
+        (value, kind) if value.kind() == kind => None,
+        _ => Some(invalid()),
+    }
+}
+
+/// Whether the number is positive and within its total
+fn is_valid_pair(number: u16, total: Option<u16>) -> bool {
+    number > 0 && total.is_none_or(|total| number <= total)
+}
+
+/// Checks the `system|label|code|` form of iTunes ratings, ratings of the known
+/// `RatingSystem`s have to be a known `ContentRating`. Unrated content has `???` as code.
+fn rating_problem(rating: &str) -> Option<AtomProblem> {
+    let fields: Vec<&str> = rating.split('|').collect();
+    let known_system = fields[0].parse::<RatingSystem>().is_ok();
+    let valid = (fields.len() == 3 || fields.len() == 4)
+        && (!known_system || rating.parse::<ContentRating>().is_ok())
+        && !fields[0].is_empty()
+        && !fields[1].is_empty()
+        && (fields[2].parse::<u32>().is_ok() || fields[2] == "???");
+    if valid {
+        None
+    } else {
+        Some(AtomProblem::InvalidRating(rating.to_owned()))
+    }
+}
+
+/// Checks that the artwork file exists and holds a supported image
+fn artwork_problem(artwork: &Artwork) -> Option<AtomProblem> {
+    match artwork.format() {
+        Ok(_) => None,
+        Err(SublerError::ArtworkNotFound(path)) => Some(AtomProblem::MissingArtwork(path)),
+        Err(_) => Some(AtomProblem::UnsupportedArtwork(artwork.to_string())),
+    }
+}
@@ -0,0 +1,161 @@
+//! Typed values of metadata atoms
+
+use std::fmt;
+
+use crate::{Artwork, ReleaseDate};
+
+/// The type of value a metadata tag holds, as declared for every tag in `Atoms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtomKind {
+    Text,
+    Integer,
+    Boolean,
+    Date,
+    Pair,
+    Image,
+}
+
+/// The value of an `Atom`
+///
+/// Its `Display` form is the text SublerCli expects in `-metadata`:
+///
+/// ```rust
+/// use sublercli::AtomValue;
+/// assert_eq!(AtomValue::Pair { number: 3, total: Some(12) }.to_string(), "3/12");
+/// assert_eq!(AtomValue::Pair { number: 3, total: None }.to_string(), "3");
+/// assert_eq!(AtomValue::Boolean(true).to_string(), "1");
+/// assert_eq!(AtomValue::Integer(120).to_string(), "120");
+/// assert_eq!(AtomValue::from("Foo Bar Title").to_string(), "Foo Bar Title");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum AtomValue {
+    /// Free text
+    Text(String),
+    /// A whole number, like the tempo or an iTunes ID
+    Integer(i64),
+    /// A flag, like `HD Video` or `Gapless`
+    Boolean(bool),
+    /// A release date, like `2018` or `2018-04-01`
+    Date(ReleaseDate),
+    /// A number-of-total pair, like `Track #` and `Disk #`
+    Pair {
+        /// The number of this item
+        number: u16,
+        /// The number of all items, if known
+        total: Option<u16>,
+    },
+    /// A cover image
+    Image(Artwork),
+}
+
+impl AtomValue {
+    /// The kind of this value
+    pub fn kind(&self) -> AtomKind {
+        match self {
+            AtomValue::Text(_) => AtomKind::Text,
+            AtomValue::Integer(_) => AtomKind::Integer,
+            AtomValue::Boolean(_) => AtomKind::Boolean,
+            AtomValue::Date(_) => AtomKind::Date,
+            AtomValue::Pair { .. } => AtomKind::Pair,
+            AtomValue::Image(_) => AtomKind::Image,
+        }
+    }
+
+    /// Parses `text` as a value of `kind`, `None` if it isn't one
+    ///
+    /// Flags are `1`, `yes` or `true` and `0`, `no` or `false`, pairs are `3` or `3/12`,
+    /// images are paths.
+    ///
+    /// ```rust
+    /// use sublercli::{AtomKind, AtomValue};
+    /// assert_eq!(
+    ///     AtomValue::parse(AtomKind::Pair, "3/12"),
+    ///     Some(AtomValue::Pair { number: 3, total: Some(12) })
+    /// );
+    /// assert_eq!(AtomValue::parse(AtomKind::Boolean, "yes"), Some(AtomValue::Boolean(true)));
+    /// assert_eq!(AtomValue::parse(AtomKind::Integer, "120 bpm"), None);
+    /// ```
+    pub fn parse(kind: AtomKind, text: &str) -> Option<AtomValue> {
+        match kind {
+            AtomKind::Text => Some(AtomValue::from(text)),
+            AtomKind::Integer => text.trim().parse().ok().map(AtomValue::Integer),
+            AtomKind::Boolean => parse_flag(text).map(AtomValue::Boolean),
+            AtomKind::Date => text.parse().ok().map(AtomValue::Date),
+            AtomKind::Pair => {
+                parse_pair(text).map(|(number, total)| AtomValue::Pair { number, total })
+            }
+            AtomKind::Image => Some(AtomValue::Image(Artwork::from_path(text))),
+        }
+    }
+
+    /// The text of `Text` values
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            AtomValue::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is empty text, which deletes the tag
+    pub fn is_empty(&self) -> bool {
+        match self {
+            AtomValue::Text(text) => text.is_empty(),
+            AtomValue::Image(artwork) => artwork
+                .path()
+                .is_some_and(|path| path.as_os_str().is_empty()),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for AtomValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtomValue::Text(text) => f.write_str(text),
+            AtomValue::Date(date) => write!(f, "{}", date),
+            AtomValue::Integer(number) => write!(f, "{}", number),
+            AtomValue::Boolean(flag) => f.write_str(if *flag { "1" } else { "0" }),
+            AtomValue::Pair {
+                number,
+                total: Some(total),
+            } => write!(f, "{}/{}", number, total),
+            AtomValue::Pair {
+                number,
+                total: None,
+            } => write!(f, "{}", number),
+            AtomValue::Image(artwork) => write!(f, "{}", artwork),
+        }
+    }
+}
+
+/// Parses a `1` or `1/12` number-of-total pair
+pub(crate) fn parse_pair(text: &str) -> Option<(u16, Option<u16>)> {
+    let mut parts = text.splitn(2, '/').map(str::trim);
+    let number = parts.next()?.parse().ok()?;
+    let total = match parts.next() {
+        Some(total) => Some(total.parse().ok()?),
+        None => None,
+    };
+    Some((number, total))
+}
+
+/// Parses the flag values understood by SublerCli and the native writer
+pub(crate) fn parse_flag(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "1" | "yes" | "true" => Some(true),
+        "0" | "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+impl<'a> From<&'a str> for AtomValue {
+    fn from(text: &'a str) -> Self {
+        AtomValue::Text(text.to_owned())
+    }
+}
+
+impl From<String> for AtomValue {
+    fn from(text: String) -> Self {
+        AtomValue::Text(text)
+    }
+}